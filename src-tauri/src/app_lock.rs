@@ -1,40 +1,62 @@
 use std::fs;
+use std::future::Future;
 use std::path::{Path, PathBuf};
 use std::sync::Mutex;
-use std::time::{SystemTime, UNIX_EPOCH};
-
-use aes_gcm::aead::{Aead, KeyInit};
-use aes_gcm::{Aes256Gcm, Nonce};
-use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
-use argon2::{Algorithm, Argon2, Params, Version};
-use base64::engine::general_purpose::STANDARD as BASE64;
-use base64::Engine;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 use rand::rngs::OsRng;
 use rand::RngCore;
 use serde::{Deserialize, Serialize};
 use tauri::{Manager, State};
+use tauri_plugin_sql::{DbInstances, SessionKey};
+
+use crate::secure_storage::{SecretChain, SecureStorageState};
+
+mod idle;
+mod keyset;
+mod open_dek;
 
-use crate::secure_storage;
+pub use idle::{handle_run_event, handle_window_event, spawn_idle_watcher};
+use idle::IdleTimer;
+use keyset::{
+    generate_recovery_key, normalize_recovery_key, KdfPolicy, KeySlot, KeySlotKind, Keyset, KEY_LENGTH,
+    PASSPHRASE_SLOT_LABEL, RECOVERY_SLOT_LABEL,
+};
+pub use open_dek::OpenDekProtection;
+use open_dek::{delete_open_dek, read_open_dek, write_open_dek, APP_LOCK_OPEN_DEK_STORAGE_KEY};
 
 const APP_LOCK_KEYSET_STORAGE_KEY: &str = "journai.app_lock.keyset";
-const APP_LOCK_OPEN_DEK_STORAGE_KEY: &str = "journai.app_lock.open_dek";
+/// The previous keyset, kept until a write retires a passphrase or recovery key.
+const APP_LOCK_KEYSET_BACKUP_STORAGE_KEY: &str = "journai.app_lock.keyset_backup";
+const APP_LOCK_THROTTLE_STORAGE_KEY: &str = "journai.app_lock.unlock_throttle";
 const APP_LOCK_PASSPHRASE_MIN_LENGTH: usize = 8;
-const SQLCIPHER_KEY_ENV_VAR: &str = "JOURNAI_SQLCIPHER_KEY_HEX";
 const SECURE_DB_FILE_NAME: &str = "journai.db";
-const KEY_LENGTH: usize = 32;
-const SALT_LENGTH: usize = 16;
-const NONCE_LENGTH: usize = 12;
+const UNLOCK_FREE_ATTEMPTS: u32 = 3;
+const UNLOCK_BACKOFF_BASE_MS: u64 = 5_000;
+const UNLOCK_BACKOFF_MAX_MS: u64 = 15 * 60 * 1_000;
+const UNLOCK_WIPE_MIN_ATTEMPTS: u32 = 5;
+const KDF_CALIBRATION_DEFAULT_MS: u64 = 1_000;
+const KDF_CALIBRATION_MIN_MS: u64 = 250;
+const KDF_CALIBRATION_MAX_MS: u64 = 5_000;
 
 pub struct AppLockRuntimeState {
     unlocked: Mutex<bool>,
     configured_cache: Mutex<Option<bool>>,
+    session_key: SessionKey,
+    unlock_gate: tauri::async_runtime::Mutex<()>,
+    idle: Mutex<IdleTimer>,
+    open_dek_protection: Mutex<Option<OpenDekProtection>>,
 }
 
-impl Default for AppLockRuntimeState {
-    fn default() -> Self {
+impl AppLockRuntimeState {
+    pub fn new(session_key: SessionKey) -> Self {
         Self {
             unlocked: Mutex::new(false),
             configured_cache: Mutex::new(None),
+            session_key,
+            unlock_gate: tauri::async_runtime::Mutex::new(()),
+            idle: Mutex::new(IdleTimer::new(now_ms())),
+            open_dek_protection: Mutex::new(None),
         }
     }
 }
@@ -44,255 +66,371 @@ impl Default for AppLockRuntimeState {
 pub struct AppLockStatus {
     pub configured: bool,
     pub unlocked: bool,
+    pub failed_attempts: u32,
+    /// Attempts left before the database is wiped, when a wipe policy is set.
+    pub remaining_attempts: Option<u32>,
+    /// Unix time in milliseconds before which unlock attempts are refused.
+    pub next_attempt_at: Option<u64>,
+    /// How the DEK is stored while the app lock is disabled.
+    pub open_dek_protection: Option<OpenDekProtection>,
 }
 
-#[derive(Serialize, Deserialize)]
+/// Failed unlock bookkeeping and the wipe policy, mirrored into the secret
+/// chain like the keyset so neither restarting the app nor deleting a file
+/// resets them.
+#[derive(Clone, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
-struct Keyset {
-    version: u8,
-    password_hash: String,
-    salt_b64: String,
-    memory_kib: u32,
-    iterations: u32,
-    parallelism: u32,
-    wrapped_dek_b64: String,
-    nonce_b64: String,
+struct UnlockThrottle {
+    failed_attempts: u32,
+    last_failed_at_ms: u64,
+    #[serde(default)]
+    wipe_after_failed_attempts: Option<u32>,
+    /// Unix time in milliseconds before which attempts are refused regardless
+    /// of `failed_attempts`. Set when a stored copy was found damaged.
+    #[serde(default)]
+    locked_until_ms: u64,
 }
 
-fn platform_kdf_memory_kib() -> u32 {
-    #[cfg(any(target_os = "ios", target_os = "android"))]
-    {
-        32 * 1024
-    }
+impl UnlockThrottle {
+    fn backoff_ms(&self) -> u64 {
+        if self.failed_attempts < UNLOCK_FREE_ATTEMPTS {
+            return 0;
+        }
 
-    #[cfg(not(any(target_os = "ios", target_os = "android")))]
-    {
-        64 * 1024
+        let exponent = (self.failed_attempts - UNLOCK_FREE_ATTEMPTS).min(16);
+        (UNLOCK_BACKOFF_BASE_MS << exponent).min(UNLOCK_BACKOFF_MAX_MS)
     }
-}
 
-fn kdf_parameters() -> Result<(u32, u32, u32, Params), String> {
-    let memory_kib = platform_kdf_memory_kib();
-    let iterations = 3;
-    let parallelism = 1;
-    let params = Params::new(memory_kib, iterations, parallelism, Some(KEY_LENGTH))
-        .map_err(|e| format!("Failed to build Argon2 parameters: {e}"))?;
+    fn next_attempt_at(&self) -> Option<u64> {
+        let backoff = self.backoff_ms();
+        let backoff_until = (backoff > 0).then(|| self.last_failed_at_ms.saturating_add(backoff));
+        let locked_until = (self.locked_until_ms > 0).then_some(self.locked_until_ms);
+        backoff_until.max(locked_until)
+    }
 
-    Ok((memory_kib, iterations, parallelism, params))
-}
+    fn record_failure(&mut self, now_ms: u64) {
+        self.failed_attempts = self.failed_attempts.saturating_add(1);
+        self.last_failed_at_ms = now_ms;
+    }
 
-fn decode_base64<const N: usize>(value: &str, label: &str) -> Result<[u8; N], String> {
-    let bytes = BASE64
-        .decode(value)
-        .map_err(|e| format!("Invalid base64 for {label}: {e}"))?;
-    if bytes.len() != N {
-        return Err(format!(
-            "Invalid decoded length for {label}: expected {N}, got {}",
-            bytes.len()
-        ));
+    fn reset(&mut self) {
+        self.failed_attempts = 0;
+        self.last_failed_at_ms = 0;
+        self.locked_until_ms = 0;
     }
-    let mut output = [0u8; N];
-    output.copy_from_slice(&bytes);
-    Ok(output)
-}
 
-fn derive_kek(passphrase: &str, salt: &[u8], params: &Params) -> Result<[u8; KEY_LENGTH], String> {
-    let mut key = [0u8; KEY_LENGTH];
-    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params.clone());
-    argon2
-        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
-        .map_err(|e| format!("Failed to derive key with Argon2id: {e}"))?;
-    Ok(key)
-}
+    fn remaining_attempts(&self) -> Option<u32> {
+        self.wipe_after_failed_attempts
+            .map(|limit| limit.saturating_sub(self.failed_attempts))
+    }
 
-fn keyset_fallback_path() -> Result<PathBuf, String> {
-    let mut base = dirs::data_local_dir()
-        .or_else(dirs::data_dir)
-        .ok_or_else(|| "Unable to resolve app data directory for app lock keyset".to_string())?;
-    base.push("journai");
+    fn should_wipe(&self) -> bool {
+        self.remaining_attempts() == Some(0)
+    }
 
-    fs::create_dir_all(&base)
-        .map_err(|e| format!("Failed to create keyset directory {}: {e}", base.display()))?;
+    /// Picks the stored copy with the most failed attempts. If any copy does
+    /// not parse, the result is locked out for the maximum backoff from
+    /// `now_ms` and the flag is set. The failed-attempt count is left as read,
+    /// so a damaged copy never brings the wipe policy closer.
+    fn from_copies(copies: &[String], now_ms: u64) -> (Self, bool) {
+        let mut throttle: Option<Self> = None;
+        let mut damaged = false;
+        for raw in copies {
+            match serde_json::from_str::<Self>(raw) {
+                Ok(copy) => {
+                    if throttle
+                        .as_ref()
+                        .is_none_or(|best| copy.failed_attempts > best.failed_attempts)
+                    {
+                        throttle = Some(copy);
+                    }
+                }
+                Err(_) => damaged = true,
+            }
+        }
 
-    base.push("app_lock_keyset.json");
-    Ok(base)
+        let mut throttle = throttle.unwrap_or_default();
+        if damaged {
+            throttle.locked_until_ms = throttle
+                .locked_until_ms
+                .max(now_ms.saturating_add(UNLOCK_BACKOFF_MAX_MS));
+        }
+        (throttle, damaged)
+    }
 }
 
-fn parse_keyset(raw: &str) -> Result<Keyset, String> {
-    serde_json::from_str(raw).map_err(|e| format!("Invalid stored app lock keyset: {e}"))
+/// The keyset file written before the keyset moved into the secret chain.
+fn legacy_keyset_path() -> Option<PathBuf> {
+    let dir = dirs::data_local_dir().or_else(dirs::data_dir)?;
+    Some(dir.join("journai").join("app_lock_keyset.json"))
 }
 
-fn write_keyset_fallback_file(raw: &str) -> Result<(), String> {
-    let path = keyset_fallback_path()?;
-    fs::write(&path, raw).map_err(|e| format!("Failed to write fallback keyset {}: {e}", path.display()))
+fn delete_legacy_keyset_file() -> Result<(), String> {
+    if let Some(path) = legacy_keyset_path().filter(|path| path.exists()) {
+        fs::remove_file(&path)
+            .map_err(|e| format!("Failed to remove legacy keyset {}: {e}", path.display()))?;
+    }
+    Ok(())
 }
 
-fn read_keyset_fallback_file() -> Result<Option<Keyset>, String> {
-    let path = keyset_fallback_path()?;
-    if !path.exists() {
-        return Ok(None);
+/// The stored copy with the highest generation, its index and whether it is
+/// in an older format.
+fn newest_keyset(copies: &[String]) -> Result<Option<(usize, Keyset, bool)>, String> {
+    let mut newest: Option<(usize, Keyset, bool)> = None;
+    let mut last_error = None;
+    for (index, raw) in copies.iter().enumerate() {
+        match Keyset::parse(raw) {
+            Ok((keyset, upgraded)) => {
+                if newest
+                    .as_ref()
+                    .is_none_or(|(_, best, _)| keyset.generation() > best.generation())
+                {
+                    newest = Some((index, keyset, upgraded));
+                }
+            }
+            Err(e) => last_error = Some(e),
+        }
+    }
+    match (newest, last_error) {
+        (None, Some(e)) => Err(e),
+        (newest, _) => Ok(newest),
     }
-
-    let raw = fs::read_to_string(&path)
-        .map_err(|e| format!("Failed to read fallback keyset {}: {e}", path.display()))?;
-    let keyset = parse_keyset(&raw)?;
-    Ok(Some(keyset))
 }
 
-fn read_keyset() -> Result<Option<Keyset>, String> {
-    match secure_storage::get_secret(APP_LOCK_KEYSET_STORAGE_KEY) {
-        Ok(Some(raw)) => {
-            let keyset = parse_keyset(&raw)?;
-            let _ = write_keyset_fallback_file(&raw);
+/// Reads the keyset from the backend copy with the highest generation, so a
+/// backend that missed a write cannot bring back an older keyset. Only when
+/// no copy parses does it fall back to the backup, then to the file left by
+/// earlier versions. The keyset is rewritten unless it came from identical
+/// current copies in the current format; a failed rewrite is recorded but
+/// does not fail the read.
+fn read_keyset(chain: &SecretChain) -> Result<Option<Keyset>, String> {
+    let current = chain.copies(APP_LOCK_KEYSET_STORAGE_KEY);
+    let legacy = legacy_keyset_path().and_then(|path| fs::read_to_string(path).ok());
+
+    let mut last_error = None;
+    let found = match newest_keyset(&current) {
+        Ok(Some((index, keyset, upgraded))) => {
+            let stale = upgraded
+                || current.iter().any(|copy| *copy != current[index])
+                || legacy.is_some();
+            Some((keyset, stale))
+        }
+        Ok(None) => None,
+        Err(e) => {
+            last_error = Some(e);
+            None
+        }
+    };
+
+    let found = found.or_else(|| {
+        let mut fallbacks = chain.copies(APP_LOCK_KEYSET_BACKUP_STORAGE_KEY);
+        fallbacks.extend(legacy);
+        fallbacks.iter().find_map(|raw| match Keyset::parse(raw) {
+            Ok((keyset, _)) => Some((keyset, true)),
+            Err(e) => {
+                last_error = Some(e);
+                None
+            }
+        })
+    });
+
+    match found {
+        Some((keyset, stale)) => {
+            if stale {
+                if let Err(e) = write_keyset(chain, &keyset) {
+                    chain.record_error(format!("Failed to rewrite the app lock keyset: {e}"));
+                }
+            }
             Ok(Some(keyset))
         }
-        Ok(None) => read_keyset_fallback_file(),
-        Err(_) => read_keyset_fallback_file(),
+        None => last_error.map_or(Ok(None), Err),
     }
 }
 
-fn write_keyset(keyset: &Keyset) -> Result<(), String> {
-    let raw = serde_json::to_string(keyset).map_err(|e| format!("Failed to serialize keyset: {e}"))?;
-    write_keyset_fallback_file(&raw)?;
-    let _ = secure_storage::set_secret(APP_LOCK_KEYSET_STORAGE_KEY, &raw);
-    Ok(())
-}
-
-fn verify_passphrase(keyset: &Keyset, passphrase: &str) -> Result<(), String> {
-    let parsed_hash = PasswordHash::new(&keyset.password_hash)
-        .map_err(|e| format!("Invalid stored password hash: {e}"))?;
-    let params = Params::new(
-        keyset.memory_kib,
-        keyset.iterations,
-        keyset.parallelism,
-        Some(KEY_LENGTH),
-    )
-    .map_err(|e| format!("Invalid stored Argon2 params: {e}"))?;
-    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+/// Mirrors the keyset into every secret backend under the next generation,
+/// first saving the newest readable previous version as the backup. Fails
+/// unless every persistent backend took it.
+fn write_keyset(chain: &SecretChain, keyset: &Keyset) -> Result<(), String> {
+    let stored = newest_keyset(&chain.copies(APP_LOCK_KEYSET_STORAGE_KEY))
+        .ok()
+        .flatten();
+
+    let mut keyset = keyset.clone();
+    let mut generation = keyset.generation();
+    if let Some((_, previous, _)) = stored {
+        generation = generation.max(previous.generation());
+        let mut unchanged = previous.clone();
+        unchanged.set_generation(keyset.generation());
+        if unchanged != keyset {
+            chain.mirror(APP_LOCK_KEYSET_BACKUP_STORAGE_KEY, &previous.to_json()?)?;
+        }
+    }
+    keyset.set_generation(generation + 1);
+    let raw = keyset.to_json()?;
 
-    argon2
-        .verify_password(passphrase.as_bytes(), &parsed_hash)
-        .map_err(|_| "Invalid passphrase".to_string())
+    chain
+        .mirror(APP_LOCK_KEYSET_STORAGE_KEY, &raw)
+        .map_err(|e| format!("Failed to save the app lock keyset: {e}"))?;
+    delete_legacy_keyset_file()
 }
 
-fn build_keyset_from_passphrase(passphrase: &str, existing_dek: Option<[u8; KEY_LENGTH]>) -> Result<Keyset, String> {
-    if passphrase.chars().count() < APP_LOCK_PASSPHRASE_MIN_LENGTH {
-        return Err(format!(
-            "Passphrase must be at least {APP_LOCK_PASSPHRASE_MIN_LENGTH} characters"
-        ));
-    }
+/// Writes a keyset that drops or replaces a passphrase or recovery key. The
+/// backup is removed so the retired secret cannot unlock it.
+fn write_keyset_retiring_secrets(chain: &SecretChain, keyset: &Keyset) -> Result<(), String> {
+    write_keyset(chain, keyset)?;
+    delete_keyset_backup(chain)
+}
 
-    let (memory_kib, iterations, parallelism, params) = kdf_parameters()?;
+fn delete_keyset_backup(chain: &SecretChain) -> Result<(), String> {
+    chain.delete(APP_LOCK_KEYSET_BACKUP_STORAGE_KEY)
+}
 
-    let mut salt = [0u8; SALT_LENGTH];
-    OsRng.fill_bytes(&mut salt);
-    let salt_string = SaltString::encode_b64(&salt)
-        .map_err(|e| format!("Failed to encode Argon2 salt: {e}"))?;
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
 
-    let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params.clone());
-    let password_hash = argon2
-        .hash_password(passphrase.as_bytes(), &salt_string)
-        .map_err(|e| format!("Failed to hash passphrase: {e}"))?
-        .to_string();
+/// Reads the throttle from the copy with the most failed attempts. A copy
+/// that does not parse locks unlocking for the maximum backoff rather than
+/// failing, so a damaged throttle neither blocks unlocking for good nor
+/// hands out attempts, and it does not count towards the wipe policy.
+fn read_unlock_throttle(chain: &SecretChain) -> Result<UnlockThrottle, String> {
+    let copies = chain.copies(APP_LOCK_THROTTLE_STORAGE_KEY);
+    let (throttle, damaged) = UnlockThrottle::from_copies(&copies, now_ms());
+    if damaged {
+        // Saving starts the backoff now rather than on every read; if it
+        // fails, the damaged copy keeps reading as locked out.
+        let _ = write_unlock_throttle(chain, &throttle);
+    }
+    Ok(throttle)
+}
 
-    let kek = derive_kek(passphrase, &salt, &params)?;
+fn write_unlock_throttle(chain: &SecretChain, throttle: &UnlockThrottle) -> Result<(), String> {
+    let raw = serde_json::to_string(throttle)
+        .map_err(|e| format!("Failed to serialize unlock throttle: {e}"))?;
+    chain
+        .mirror(APP_LOCK_THROTTLE_STORAGE_KEY, &raw)
+        .map(|_| ())
+        .map_err(|e| format!("Failed to save the unlock throttle: {e}"))
+}
 
-    let mut dek = existing_dek.unwrap_or([0u8; KEY_LENGTH]);
-    if existing_dek.is_none() {
-        OsRng.fill_bytes(&mut dek);
-    }
-
-    let mut nonce = [0u8; NONCE_LENGTH];
-    OsRng.fill_bytes(&mut nonce);
-
-    let cipher = Aes256Gcm::new_from_slice(&kek)
-        .map_err(|e| format!("Failed to initialize cipher: {e}"))?;
-    let wrapped_dek = cipher
-        .encrypt(Nonce::from_slice(&nonce), dek.as_ref())
-        .map_err(|e| format!("Failed to wrap DEK: {e}"))?;
-
-    Ok(Keyset {
-        version: 1,
-        password_hash,
-        salt_b64: BASE64.encode(salt),
-        memory_kib,
-        iterations,
-        parallelism,
-        wrapped_dek_b64: BASE64.encode(wrapped_dek),
-        nonce_b64: BASE64.encode(nonce),
-    })
+fn reset_unlock_throttle(chain: &SecretChain) -> Result<(), String> {
+    let mut throttle = read_unlock_throttle(chain)?;
+    if throttle.failed_attempts == 0 && throttle.locked_until_ms == 0 {
+        return Ok(());
+    }
+    throttle.reset();
+    write_unlock_throttle(chain, &throttle)
 }
 
-fn unwrap_dek(keyset: &Keyset, passphrase: &str) -> Result<[u8; KEY_LENGTH], String> {
-    verify_passphrase(keyset, passphrase)?;
+fn delete_unlock_throttle(chain: &SecretChain) -> Result<(), String> {
+    chain.delete(APP_LOCK_THROTTLE_STORAGE_KEY)
+}
 
-    let params = Params::new(
-        keyset.memory_kib,
-        keyset.iterations,
-        keyset.parallelism,
-        Some(KEY_LENGTH),
-    )
-    .map_err(|e| format!("Invalid stored Argon2 params: {e}"))?;
-    let salt = decode_base64::<SALT_LENGTH>(&keyset.salt_b64, "salt")?;
-    let kek = derive_kek(passphrase, &salt, &params)?;
+fn kdf_policy_path() -> Result<PathBuf, String> {
+    let mut base = dirs::data_local_dir()
+        .or_else(dirs::data_dir)
+        .ok_or_else(|| "Unable to resolve app data directory for KDF policy".to_string())?;
+    base.push("journai");
 
-    let wrapped_dek = BASE64
-        .decode(&keyset.wrapped_dek_b64)
-        .map_err(|e| format!("Invalid wrapped DEK encoding: {e}"))?;
-    let nonce = decode_base64::<NONCE_LENGTH>(&keyset.nonce_b64, "nonce")?;
+    fs::create_dir_all(&base)
+        .map_err(|e| format!("Failed to create KDF policy directory {}: {e}", base.display()))?;
 
-    let cipher = Aes256Gcm::new_from_slice(&kek)
-        .map_err(|e| format!("Failed to initialize cipher: {e}"))?;
-    let unwrapped = cipher
-        .decrypt(Nonce::from_slice(&nonce), wrapped_dek.as_ref())
-        .map_err(|_| "Invalid passphrase".to_string())?;
+    base.push("app_lock_kdf_policy.json");
+    Ok(base)
+}
 
-    if unwrapped.len() != KEY_LENGTH {
-        return Err("Invalid unwrapped DEK length".to_string());
+/// Policy new and upgraded slots are sealed with: the calibrated policy for
+/// this machine when one was saved, kept within the calibration limits and
+/// never weaker than the platform default.
+fn target_kdf_policy() -> KdfPolicy {
+    let calibrated = kdf_policy_path()
+        .and_then(|path| fs::read_to_string(path).map_err(|e| e.to_string()))
+        .ok()
+        .and_then(|raw| serde_json::from_str::<KdfPolicy>(&raw).ok());
+
+    match calibrated {
+        Some(policy) => policy.capped().strongest(KdfPolicy::current()),
+        None => KdfPolicy::current(),
     }
+}
 
-    let mut dek = [0u8; KEY_LENGTH];
-    dek.copy_from_slice(&unwrapped);
-    Ok(dek)
+fn write_kdf_policy(policy: &KdfPolicy) -> Result<(), String> {
+    let raw = serde_json::to_string(policy).map_err(|e| format!("Failed to serialize KDF policy: {e}"))?;
+    let path = kdf_policy_path()?;
+    fs::write(&path, raw).map_err(|e| format!("Failed to write KDF policy {}: {e}", path.display()))
 }
 
-fn encode_hex(bytes: &[u8]) -> String {
-    let mut output = String::with_capacity(bytes.len() * 2);
-    for byte in bytes {
-        output.push_str(&format!("{byte:02x}"));
+fn passphrase_slot(passphrase: &str, dek: &[u8; KEY_LENGTH]) -> Result<KeySlot, String> {
+    if passphrase.chars().count() < APP_LOCK_PASSPHRASE_MIN_LENGTH {
+        return Err(format!(
+            "Passphrase must be at least {APP_LOCK_PASSPHRASE_MIN_LENGTH} characters"
+        ));
     }
-    output
+
+    KeySlot::seal(
+        KeySlotKind::Passphrase,
+        PASSPHRASE_SLOT_LABEL,
+        passphrase.as_bytes(),
+        dek,
+        target_kdf_policy(),
+    )
 }
 
-fn set_sqlcipher_session_key(dek: &[u8; KEY_LENGTH]) {
-    std::env::set_var(SQLCIPHER_KEY_ENV_VAR, encode_hex(dek));
+fn recovery_slot(recovery_key: &str, dek: &[u8; KEY_LENGTH]) -> Result<KeySlot, String> {
+    let normalized = normalize_recovery_key(recovery_key)?;
+    KeySlot::seal(
+        KeySlotKind::RecoveryKey,
+        RECOVERY_SLOT_LABEL,
+        normalized.as_bytes(),
+        dek,
+        target_kdf_policy(),
+    )
 }
 
-fn clear_sqlcipher_session_key() {
-    std::env::remove_var(SQLCIPHER_KEY_ENV_VAR);
+fn unwrap_dek(keyset: &Keyset, passphrase: &str) -> Result<[u8; KEY_LENGTH], String> {
+    keyset.open(KeySlotKind::Passphrase, passphrase.as_bytes())
 }
 
-fn is_sqlcipher_key_set() -> bool {
-    std::env::var(SQLCIPHER_KEY_ENV_VAR)
-        .map(|v| !v.trim().is_empty())
-        .unwrap_or(false)
+fn unwrap_dek_with_recovery_key(keyset: &Keyset, recovery_key: &str) -> Result<[u8; KEY_LENGTH], String> {
+    let normalized = normalize_recovery_key(recovery_key)?;
+    keyset.open(KeySlotKind::RecoveryKey, normalized.as_bytes())
 }
 
-fn ensure_sqlcipher_key_set() -> Result<(), String> {
-    if is_sqlcipher_key_set() {
-        return Ok(());
+/// Loads (or creates) the open DEK into the session key and returns how it is
+/// stored. Returns `None` when the key was already set this session.
+fn ensure_sqlcipher_key_set(
+    chain: &SecretChain,
+    session_key: &SessionKey,
+) -> Result<Option<OpenDekProtection>, String> {
+    if session_key.is_set() {
+        return Ok(None);
     }
 
-    let dek = match read_open_dek()? {
-        Some(dek) => dek,
+    let (dek, protection) = match read_open_dek(chain)? {
+        Some(stored) => stored,
         None => {
             let mut dek = [0u8; KEY_LENGTH];
             OsRng.fill_bytes(&mut dek);
-            write_open_dek(&dek)?;
-            dek
+            let protection = write_open_dek(chain, &dek)?;
+            (dek, protection)
         }
     };
-    set_sqlcipher_session_key(&dek);
+    session_key.set(&dek);
+    Ok(Some(protection))
+}
+
+fn set_open_dek_protection(
+    runtime: &State<'_, AppLockRuntimeState>,
+    value: Option<OpenDekProtection>,
+) -> Result<(), String> {
+    let mut guard = runtime
+        .open_dek_protection
+        .lock()
+        .map_err(|_| "Failed to acquire app lock state".to_string())?;
+    *guard = value;
     Ok(())
 }
 
@@ -357,6 +495,54 @@ fn backup_and_reset_secure_database(app: &tauri::AppHandle) -> Result<Option<Pat
     Ok(db_backup_path)
 }
 
+/// Copies the database and its WAL/SHM companions aside so a failed rekey can
+/// be undone. Companions that do not exist yet are recorded without a copy.
+fn snapshot_secure_database(db_path: &Path) -> Result<Vec<(PathBuf, Option<PathBuf>)>, String> {
+    let mut snapshot = Vec::new();
+    for path in [
+        db_path.to_path_buf(),
+        append_path_suffix(db_path, "-wal"),
+        append_path_suffix(db_path, "-shm"),
+    ] {
+        let copy = if path.exists() {
+            let copy_path = append_path_suffix(&path, ".rekey-backup");
+            if let Err(e) = fs::copy(&path, &copy_path) {
+                discard_secure_database_snapshot(&snapshot);
+                return Err(format!(
+                    "Failed to copy {} to {}: {e}",
+                    path.display(),
+                    copy_path.display()
+                ));
+            }
+            Some(copy_path)
+        } else {
+            None
+        };
+        snapshot.push((path, copy));
+    }
+    Ok(snapshot)
+}
+
+fn restore_secure_database_snapshot(snapshot: &[(PathBuf, Option<PathBuf>)]) -> Result<(), String> {
+    for (path, copy) in snapshot {
+        match copy {
+            Some(copy) => fs::rename(copy, path).map_err(|e| {
+                format!("Failed to restore {} from {}: {e}", path.display(), copy.display())
+            })?,
+            None if path.exists() => fs::remove_file(path)
+                .map_err(|e| format!("Failed to remove {}: {e}", path.display()))?,
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+fn discard_secure_database_snapshot(snapshot: &[(PathBuf, Option<PathBuf>)]) {
+    for copy in snapshot.iter().filter_map(|(_, copy)| copy.as_ref()) {
+        let _ = fs::remove_file(copy);
+    }
+}
+
 fn set_runtime_configured(runtime: &State<'_, AppLockRuntimeState>, value: bool) -> Result<(), String> {
     let mut guard = runtime
         .configured_cache
@@ -372,6 +558,18 @@ fn set_runtime_unlocked(runtime: &State<'_, AppLockRuntimeState>, value: bool) -
         .lock()
         .map_err(|_| "Failed to acquire app lock state".to_string())?;
     *guard = value;
+    if value {
+        touch_idle_timer(runtime)?;
+    }
+    Ok(())
+}
+
+fn touch_idle_timer(runtime: &State<'_, AppLockRuntimeState>) -> Result<(), String> {
+    runtime
+        .idle
+        .lock()
+        .map_err(|_| "Failed to acquire app lock state".to_string())?
+        .touch(now_ms());
     Ok(())
 }
 
@@ -384,7 +582,11 @@ fn runtime_is_unlocked(runtime: &State<'_, AppLockRuntimeState>) -> Result<bool,
 }
 
 #[tauri::command]
-pub async fn app_lock_status(runtime: State<'_, AppLockRuntimeState>) -> Result<AppLockStatus, String> {
+pub async fn app_lock_status(
+    runtime: State<'_, AppLockRuntimeState>,
+    storage: State<'_, SecureStorageState>,
+) -> Result<AppLockStatus, String> {
+    let chain = storage.chain();
     let cached = {
         let guard = runtime
             .configured_cache
@@ -396,8 +598,9 @@ pub async fn app_lock_status(runtime: State<'_, AppLockRuntimeState>) -> Result<
     let configured = match cached {
         Some(val) => val,
         None => {
-            let result = tauri::async_runtime::spawn_blocking(|| {
-                read_keyset().map(|ks| ks.is_some())
+            let chain = chain.clone();
+            let result = tauri::async_runtime::spawn_blocking(move || {
+                read_keyset(&chain).map(|ks| ks.is_some())
             })
             .await
             .map_err(|e| format!("Task failed: {e}"))??;
@@ -412,56 +615,92 @@ pub async fn app_lock_status(runtime: State<'_, AppLockRuntimeState>) -> Result<
     };
 
     if !configured {
-        tauri::async_runtime::spawn_blocking(ensure_sqlcipher_key_set)
+        let session_key = runtime.session_key.clone();
+        let loaded = tauri::async_runtime::spawn_blocking(move || ensure_sqlcipher_key_set(&chain, &session_key))
             .await
             .map_err(|e| format!("Task failed: {e}"))??;
+        if loaded.is_some() {
+            set_open_dek_protection(&runtime, loaded)?;
+        }
         set_runtime_unlocked(&runtime, true)?;
+        let open_dek_protection = *runtime
+            .open_dek_protection
+            .lock()
+            .map_err(|_| "Failed to acquire app lock state".to_string())?;
         return Ok(AppLockStatus {
             configured: false,
             unlocked: true,
+            failed_attempts: 0,
+            remaining_attempts: None,
+            next_attempt_at: None,
+            open_dek_protection,
         });
     }
 
     let unlocked = runtime_is_unlocked(&runtime)?;
     if !unlocked {
-        clear_sqlcipher_session_key();
+        runtime.session_key.clear();
     }
+    let throttle = read_unlock_throttle(&chain)?;
     Ok(AppLockStatus {
         configured: true,
         unlocked,
+        failed_attempts: throttle.failed_attempts,
+        remaining_attempts: throttle.remaining_attempts(),
+        next_attempt_at: throttle.next_attempt_at().filter(|at| *at > now_ms()),
+        open_dek_protection: None,
     })
 }
 
+/// Configures the app lock. When `create_recovery_key` is set, the generated
+/// recovery key is returned once and never stored in plaintext.
 #[tauri::command]
 pub async fn app_lock_configure(
     passphrase: String,
+    create_recovery_key: Option<bool>,
     runtime: State<'_, AppLockRuntimeState>,
+    storage: State<'_, SecureStorageState>,
     app: tauri::AppHandle,
-) -> Result<(), String> {
-    if read_keyset()?.is_some() {
+) -> Result<Option<String>, String> {
+    let chain = storage.chain();
+    if read_keyset(&chain)?.is_some() {
         return Err("App lock is already configured. Unlock with your existing passphrase.".to_string());
     }
 
-    let existing_dek = read_open_dek()?;
+    let existing_dek = read_open_dek(&chain)?.map(|(dek, _)| dek);
 
     if existing_dek.is_none() {
         backup_and_reset_secure_database(&app)?;
     }
 
-    let pp = passphrase.clone();
-    let (keyset, dek) = tauri::async_runtime::spawn_blocking(move || {
-        let ks = build_keyset_from_passphrase(&pp, existing_dek)?;
-        let dk = unwrap_dek(&ks, &pp)?;
-        Ok::<_, String>((ks, dk))
+    let (keyset, dek, recovery_key) = tauri::async_runtime::spawn_blocking(move || {
+        let dk = existing_dek.unwrap_or_else(|| {
+            let mut dk = [0u8; KEY_LENGTH];
+            OsRng.fill_bytes(&mut dk);
+            dk
+        });
+        let mut ks = Keyset::new();
+        ks.put_slot(passphrase_slot(&passphrase, &dk)?);
+        let rk = if create_recovery_key.unwrap_or(false) {
+            let rk = generate_recovery_key();
+            ks.put_slot(recovery_slot(&rk, &dk)?);
+            Some(rk)
+        } else {
+            None
+        };
+        Ok::<_, String>((ks, dk, rk))
     })
     .await
     .map_err(|e| format!("Configure task failed: {e}"))??;
 
-    write_keyset(&keyset)?;
-    let _ = delete_open_dek();
-    set_sqlcipher_session_key(&dek);
+    write_keyset(&chain, &keyset)?;
+    let _ = delete_open_dek(&chain);
+    let _ = delete_unlock_throttle(&chain);
+    runtime.session_key.set(&dek);
+    set_open_dek_protection(&runtime, None)?;
     set_runtime_configured(&runtime, true)?;
-    set_runtime_unlocked(&runtime, true)
+    set_runtime_unlocked(&runtime, true)?;
+    Ok(recovery_key)
 }
 
 #[tauri::command]
@@ -470,135 +709,498 @@ pub fn app_lock_backup_and_reset_secure_db(app: tauri::AppHandle) -> Result<Opti
     Ok(backup_path.map(|path| path.to_string_lossy().to_string()))
 }
 
-#[tauri::command]
-pub async fn app_lock_unlock(passphrase: String, runtime: State<'_, AppLockRuntimeState>) -> Result<bool, String> {
-    let Some(keyset) = read_keyset()? else {
-        clear_sqlcipher_session_key();
-        set_runtime_unlocked(&runtime, false)?;
-        return Err("App lock is configured but key material is unavailable.".to_string());
-    };
+/// Clears the session key and closes every SQL pool so nothing stays readable
+/// until the next unlock reopens them.
+async fn lock_session(runtime: &State<'_, AppLockRuntimeState>, db_instances: &DbInstances) -> Result<(), String> {
+    runtime.session_key.clear();
+    db_instances.close_all().await;
+    set_runtime_unlocked(runtime, false)
+}
 
-    let result = tauri::async_runtime::spawn_blocking(move || {
-        unwrap_dek(&keyset, &passphrase)
-    })
-    .await
-    .map_err(|e| format!("Unlock task failed: {e}"))?;
+/// Destroys the key material and moves the database aside after too many
+/// failed unlock attempts. The backup cannot be decrypted without the keyset.
+async fn wipe_after_failed_unlocks(
+    chain: &SecretChain,
+    runtime: &State<'_, AppLockRuntimeState>,
+    db_instances: &DbInstances,
+    app: &tauri::AppHandle,
+) -> Result<(), String> {
+    lock_session(runtime, db_instances).await?;
+    backup_and_reset_secure_database(app)?;
+    delete_keyset(chain)?;
+    let _ = delete_open_dek(chain);
+    delete_unlock_throttle(chain)?;
+    set_runtime_configured(runtime, false)
+}
+
+/// Runs `unwrap` while enforcing the failed-attempt backoff and wipe policy.
+/// Returns `None` when the secret is rejected.
+async fn unwrap_dek_throttled<F>(
+    unwrap: F,
+    chain: &SecretChain,
+    runtime: &State<'_, AppLockRuntimeState>,
+    db_instances: &DbInstances,
+    app: &tauri::AppHandle,
+) -> Result<Option<[u8; KEY_LENGTH]>, String>
+where
+    F: FnOnce() -> Result<[u8; KEY_LENGTH], String> + Send + 'static,
+{
+    let _gate = runtime.unlock_gate.lock().await;
+
+    let mut throttle = read_unlock_throttle(chain)?;
+    if let Some(next_attempt_at) = throttle.next_attempt_at() {
+        let now = now_ms();
+        if next_attempt_at > now {
+            let wait_secs = (next_attempt_at - now).div_ceil(1_000);
+            return Err(format!(
+                "Too many failed unlock attempts. Try again in {wait_secs} seconds."
+            ));
+        }
+    }
+
+    let result = tauri::async_runtime::spawn_blocking(unwrap)
+        .await
+        .map_err(|e| format!("Unlock task failed: {e}"))?;
 
     match result {
         Ok(dek) => {
-            set_sqlcipher_session_key(&dek);
-            set_runtime_unlocked(&runtime, true)?;
-            Ok(true)
+            reset_unlock_throttle(chain)?;
+            Ok(Some(dek))
+        }
+        Err(_) => {
+            throttle.record_failure(now_ms());
+            write_unlock_throttle(chain, &throttle)?;
+
+            if throttle.should_wipe() {
+                wipe_after_failed_unlocks(chain, runtime, db_instances, app).await?;
+            }
+            Ok(None)
         }
-        Err(_) => Ok(false),
     }
 }
 
 #[tauri::command]
-pub fn app_lock_lock(runtime: State<'_, AppLockRuntimeState>) -> Result<(), String> {
-    clear_sqlcipher_session_key();
-    set_runtime_unlocked(&runtime, false)
-}
+pub async fn app_lock_unlock(
+    passphrase: String,
+    runtime: State<'_, AppLockRuntimeState>,
+    storage: State<'_, SecureStorageState>,
+    db_instances: State<'_, DbInstances>,
+    app: tauri::AppHandle,
+) -> Result<bool, String> {
+    let chain = storage.chain();
+    let Some(keyset) = read_keyset(&chain)? else {
+        lock_session(&runtime, &db_instances).await?;
+        return Err("App lock is configured but key material is unavailable.".to_string());
+    };
+
+    let existing = keyset.clone();
+    let secret = passphrase.clone();
+    let unwrap = move || unwrap_dek(&existing, &secret);
+    let Some(dek) = unwrap_dek_throttled(unwrap, &chain, &runtime, &db_instances, &app).await? else {
+        return Ok(false);
+    };
 
-fn delete_keyset() -> Result<(), String> {
-    let _ = secure_storage::delete_secret(APP_LOCK_KEYSET_STORAGE_KEY);
+    runtime.session_key.set(&dek);
+    set_runtime_unlocked(&runtime, true)?;
 
-    if let Ok(path) = keyset_fallback_path() {
-        if path.exists() {
-            fs::remove_file(&path)
-                .map_err(|e| format!("Failed to remove fallback keyset {}: {e}", path.display()))?;
+    // Slots sealed under an older, weaker KDF policy are re-wrapped now that
+    // the passphrase is known. Failing here must not fail the unlock.
+    let _ = tauri::async_runtime::spawn_blocking(move || {
+        let mut keyset = keyset;
+        if keyset.upgrade_slots(KeySlotKind::Passphrase, passphrase.as_bytes(), &dek, target_kdf_policy())? {
+            write_keyset(&chain, &keyset)?;
         }
+        Ok::<_, String>(())
+    })
+    .await;
+
+    Ok(true)
+}
+
+/// Unlocks with the recovery key and replaces the forgotten passphrase. The
+/// recovery slot is kept so the same recovery key keeps working.
+#[tauri::command]
+pub async fn app_lock_unlock_with_recovery(
+    recovery_key: String,
+    new_passphrase: String,
+    runtime: State<'_, AppLockRuntimeState>,
+    storage: State<'_, SecureStorageState>,
+    db_instances: State<'_, DbInstances>,
+    app: tauri::AppHandle,
+) -> Result<bool, String> {
+    let chain = storage.chain();
+    if new_passphrase.chars().count() < APP_LOCK_PASSPHRASE_MIN_LENGTH {
+        return Err(format!(
+            "Passphrase must be at least {APP_LOCK_PASSPHRASE_MIN_LENGTH} characters"
+        ));
     }
 
-    Ok(())
+    let Some(mut keyset) = read_keyset(&chain)? else {
+        return Err("App lock is not enabled".to_string());
+    };
+    if !keyset.has_slot_kind(KeySlotKind::RecoveryKey) {
+        return Err("No recovery key is configured for this app lock.".to_string());
+    }
+
+    let existing = keyset.clone();
+    let secret = recovery_key.clone();
+    let unwrap = move || unwrap_dek_with_recovery_key(&existing, &secret);
+    let Some(dek) = unwrap_dek_throttled(unwrap, &chain, &runtime, &db_instances, &app).await? else {
+        return Ok(false);
+    };
+
+    let keyset = tauri::async_runtime::spawn_blocking(move || {
+        keyset.put_slot(passphrase_slot(&new_passphrase, &dek)?);
+        let normalized = normalize_recovery_key(&recovery_key)?;
+        keyset.upgrade_slots(KeySlotKind::RecoveryKey, normalized.as_bytes(), &dek, target_kdf_policy())?;
+        Ok::<_, String>(keyset)
+    })
+    .await
+    .map_err(|e| format!("Recovery task failed: {e}"))??;
+
+    write_keyset_retiring_secrets(&chain, &keyset)?;
+    runtime.session_key.set(&dek);
+    set_runtime_unlocked(&runtime, true)?;
+    Ok(true)
 }
 
-fn open_dek_fallback_path() -> Result<PathBuf, String> {
-    let mut base = dirs::data_local_dir()
-        .or_else(dirs::data_dir)
-        .ok_or_else(|| "Unable to resolve app data directory for open DEK".to_string())?;
-    base.push("journai");
+/// Replaces the recovery slot with a freshly generated recovery key and
+/// returns it. Any previous recovery key stops working.
+#[tauri::command]
+pub async fn app_lock_regenerate_recovery_key(
+    passphrase: String,
+    runtime: State<'_, AppLockRuntimeState>,
+    storage: State<'_, SecureStorageState>,
+    db_instances: State<'_, DbInstances>,
+    app: tauri::AppHandle,
+) -> Result<String, String> {
+    let chain = storage.chain();
+    let Some(mut keyset) = read_keyset(&chain)? else {
+        return Err("App lock is not enabled".to_string());
+    };
 
-    fs::create_dir_all(&base)
-        .map_err(|e| format!("Failed to create open DEK directory {}: {e}", base.display()))?;
+    let existing = keyset.clone();
+    let unwrap = move || unwrap_dek(&existing, &passphrase);
+    let dek = unwrap_dek_throttled(unwrap, &chain, &runtime, &db_instances, &app)
+        .await?
+        .ok_or_else(|| "Invalid passphrase".to_string())?;
 
-    base.push("app_lock_open_dek.txt");
-    Ok(base)
+    let (keyset, recovery_key) = tauri::async_runtime::spawn_blocking(move || {
+        let rk = generate_recovery_key();
+        keyset.put_slot(recovery_slot(&rk, &dek)?);
+        Ok::<_, String>((keyset, rk))
+    })
+    .await
+    .map_err(|e| format!("Recovery key task failed: {e}"))??;
+
+    write_keyset_retiring_secrets(&chain, &keyset)?;
+    Ok(recovery_key)
 }
 
-fn write_open_dek(dek: &[u8; KEY_LENGTH]) -> Result<(), String> {
-    let hex = encode_hex(dek);
-    let path = open_dek_fallback_path()?;
-    fs::write(&path, &hex)
-        .map_err(|e| format!("Failed to write open DEK {}: {e}", path.display()))?;
-    let _ = secure_storage::set_secret(APP_LOCK_OPEN_DEK_STORAGE_KEY, &hex);
-    Ok(())
+#[tauri::command]
+pub async fn app_lock_revoke_recovery_key(
+    passphrase: String,
+    runtime: State<'_, AppLockRuntimeState>,
+    storage: State<'_, SecureStorageState>,
+    db_instances: State<'_, DbInstances>,
+    app: tauri::AppHandle,
+) -> Result<(), String> {
+    let chain = storage.chain();
+    let Some(mut keyset) = read_keyset(&chain)? else {
+        return Err("App lock is not enabled".to_string());
+    };
+
+    let existing = keyset.clone();
+    let unwrap = move || unwrap_dek(&existing, &passphrase);
+    unwrap_dek_throttled(unwrap, &chain, &runtime, &db_instances, &app)
+        .await?
+        .ok_or_else(|| "Invalid passphrase".to_string())?;
+
+    keyset.remove_slot(RECOVERY_SLOT_LABEL);
+    write_keyset_retiring_secrets(&chain, &keyset)
 }
 
-fn decode_hex_key(hex: &str) -> Option<[u8; KEY_LENGTH]> {
-    let trimmed = hex.trim();
-    if trimmed.len() != KEY_LENGTH * 2 || !trimmed.chars().all(|c| c.is_ascii_hexdigit()) {
-        return None;
+/// Re-encrypts the secure database under a newly generated DEK. With the app
+/// lock enabled, `passphrase` must unlock the keyset, which is replaced; a
+/// configured recovery key wraps the old DEK, so it is replaced as well and
+/// the new one is returned once. With the app lock disabled, the new DEK is
+/// stored the same way as the open DEK it replaces.
+///
+/// The database files and the stored key material are saved first; if the
+/// rekey or storing the new DEK fails, they are put back.
+#[tauri::command]
+pub async fn app_lock_rotate_data_key(
+    passphrase: Option<String>,
+    runtime: State<'_, AppLockRuntimeState>,
+    storage: State<'_, SecureStorageState>,
+    db_instances: State<'_, DbInstances>,
+    app: tauri::AppHandle,
+) -> Result<Option<String>, String> {
+    let chain = storage.chain();
+    let keyset = read_keyset(&chain)?;
+    if !runtime_is_unlocked(&runtime)? {
+        return Err("Unlock JournAi before rotating the data key.".to_string());
     }
 
-    let mut key = [0u8; KEY_LENGTH];
-    for i in 0..KEY_LENGTH {
-        key[i] = u8::from_str_radix(&trimmed[i * 2..i * 2 + 2], 16).ok()?;
+    let (current_dek, new_dek, rotated, recovery_key) = match keyset {
+        Some(keyset) => {
+            let passphrase =
+                passphrase.ok_or_else(|| "Enter your passphrase to rotate the data key.".to_string())?;
+            let existing = keyset.clone();
+            let secret = passphrase.clone();
+            let unwrap = move || unwrap_dek(&existing, &secret);
+            let current_dek = unwrap_dek_throttled(unwrap, &chain, &runtime, &db_instances, &app)
+                .await?
+                .ok_or_else(|| "Invalid passphrase".to_string())?;
+
+            let create_recovery_key = keyset.has_slot_kind(KeySlotKind::RecoveryKey);
+            let (rotated, new_dek, recovery_key) = tauri::async_runtime::spawn_blocking(move || {
+                let mut dk = [0u8; KEY_LENGTH];
+                OsRng.fill_bytes(&mut dk);
+                let mut ks = Keyset::new();
+                ks.put_slot(passphrase_slot(&passphrase, &dk)?);
+                let rk = if create_recovery_key {
+                    let rk = generate_recovery_key();
+                    ks.put_slot(recovery_slot(&rk, &dk)?);
+                    Some(rk)
+                } else {
+                    None
+                };
+                Ok::<_, String>((ks, dk, rk))
+            })
+            .await
+            .map_err(|e| format!("Rotate task failed: {e}"))??;
+            (current_dek, new_dek, RotatedKey::Keyset(rotated), recovery_key)
+        }
+        None => {
+            let Some((current_dek, _)) = read_open_dek(&chain)? else {
+                return Err("The secure database has no data key to rotate yet.".to_string());
+            };
+            let mut new_dek = [0u8; KEY_LENGTH];
+            OsRng.fill_bytes(&mut new_dek);
+            (current_dek, new_dek, RotatedKey::OpenDek, None)
+        }
+    };
+
+    let db_path = secure_db_path(&app)?;
+    let current_key = SessionKey::new();
+    current_key.set(&current_dek);
+    let new_key = SessionKey::new();
+    new_key.set(&new_dek);
+
+    let _gate = runtime.unlock_gate.lock().await;
+    // With the shared key cleared, closed pools cannot reconnect mid-rekey.
+    runtime.session_key.clear();
+    db_instances.close_all().await;
+
+    let rekey = || async {
+        db_instances
+            .rekey(&app, crate::SECURE_DB_URL, &current_key, &new_key)
+            .await
+            .map_err(|e| e.to_string())
+    };
+    let protection =
+        swap_data_key(&chain, &runtime.session_key, &db_path, &current_dek, &new_dek, &rotated, rekey).await?;
+    if protection.is_some() {
+        set_open_dek_protection(&runtime, protection)?;
     }
-    Some(key)
+    Ok(recovery_key)
 }
 
-fn read_open_dek() -> Result<Option<[u8; KEY_LENGTH]>, String> {
-    let hex = match secure_storage::get_secret(APP_LOCK_OPEN_DEK_STORAGE_KEY) {
-        Ok(Some(val)) => Some(val),
-        _ => {
-            let path = open_dek_fallback_path()?;
-            if path.exists() {
-                Some(
-                    fs::read_to_string(&path)
-                        .map_err(|e| format!("Failed to read open DEK {}: {e}", path.display()))?,
-                )
-            } else {
-                None
-            }
+/// Where a rotated DEK is stored.
+enum RotatedKey {
+    /// Wrapped in a new keyset, which retires the backup.
+    Keyset(Keyset),
+    /// As the open DEK, while the app lock is disabled.
+    OpenDek,
+}
+
+impl RotatedKey {
+    /// The secrets replaced when the DEK is stored.
+    fn storage_keys(&self) -> &'static [&'static str] {
+        match self {
+            RotatedKey::Keyset(_) => &[APP_LOCK_KEYSET_STORAGE_KEY, APP_LOCK_KEYSET_BACKUP_STORAGE_KEY],
+            RotatedKey::OpenDek => &[APP_LOCK_OPEN_DEK_STORAGE_KEY],
         }
-    };
+    }
 
-    match hex {
-        Some(val) => Ok(decode_hex_key(&val)),
-        None => Ok(None),
+    fn store(&self, chain: &SecretChain, dek: &[u8; KEY_LENGTH]) -> Result<Option<OpenDekProtection>, String> {
+        match self {
+            RotatedKey::Keyset(keyset) => write_keyset_retiring_secrets(chain, keyset).map(|()| None),
+            RotatedKey::OpenDek => write_open_dek(chain, dek).map(Some),
+        }
     }
 }
 
-fn delete_open_dek() -> Result<(), String> {
-    let _ = secure_storage::delete_secret(APP_LOCK_OPEN_DEK_STORAGE_KEY);
+/// Re-encrypts the closed database with `rekey` and stores the new DEK as
+/// `rotated` says, then leaves `session_key` on whichever DEK opens the
+/// database. If either step fails, the database files and every backend's
+/// copy of the replaced secrets are put back exactly as they were. Returns
+/// the new open DEK's protection when it was stored as one.
+async fn swap_data_key<F, Fut>(
+    chain: &SecretChain,
+    session_key: &SessionKey,
+    db_path: &Path,
+    current_dek: &[u8; KEY_LENGTH],
+    new_dek: &[u8; KEY_LENGTH],
+    rotated: &RotatedKey,
+    rekey: F,
+) -> Result<Option<OpenDekProtection>, String>
+where
+    F: FnOnce() -> Fut,
+    Fut: Future<Output = Result<(), String>>,
+{
+    let snapshots = rotated
+        .storage_keys()
+        .iter()
+        .map(|key| chain.snapshot(key).map(|copies| (*key, copies)))
+        .collect::<Result<Vec<_>, String>>()
+        .and_then(|stored| Ok((stored, snapshot_secure_database(db_path)?)));
+    let (stored, database) = match snapshots {
+        Ok(snapshots) => snapshots,
+        Err(e) => {
+            session_key.set(current_dek);
+            return Err(e);
+        }
+    };
 
-    if let Ok(path) = open_dek_fallback_path() {
-        if path.exists() {
-            fs::remove_file(&path)
-                .map_err(|e| format!("Failed to remove open DEK {}: {e}", path.display()))?;
+    let result = match rekey().await {
+        Ok(()) => rotated.store(chain, new_dek),
+        Err(e) => Err(format!("Failed to re-encrypt the secure database: {e}")),
+    };
+
+    match result {
+        Ok(protection) => {
+            discard_secure_database_snapshot(&database);
+            session_key.set(new_dek);
+            Ok(protection)
+        }
+        Err(mut e) => {
+            if let Err(restore_error) = restore_secure_database_snapshot(&database) {
+                e = format!("{e}. Restoring the previous database also failed: {restore_error}");
+            }
+            for (key, copies) in &stored {
+                if let Err(restore_error) = chain.restore(key, copies) {
+                    e = format!("{e}. Restoring the previous key material also failed: {restore_error}");
+                    break;
+                }
+            }
+            session_key.set(current_dek);
+            Err(e)
         }
     }
+}
+
+#[tauri::command]
+pub fn app_lock_set_wipe_policy(
+    max_failed_attempts: Option<u32>,
+    runtime: State<'_, AppLockRuntimeState>,
+    storage: State<'_, SecureStorageState>,
+) -> Result<(), String> {
+    let chain = storage.chain();
+    if read_keyset(&chain)?.is_none() {
+        return Err("App lock is not enabled".to_string());
+    }
+    if !runtime_is_unlocked(&runtime)? {
+        return Err("Unlock JournAi before changing the wipe policy.".to_string());
+    }
+    if let Some(limit) = max_failed_attempts {
+        if limit < UNLOCK_WIPE_MIN_ATTEMPTS {
+            return Err(format!(
+                "Wipe policy must allow at least {UNLOCK_WIPE_MIN_ATTEMPTS} failed attempts"
+            ));
+        }
+    }
+
+    let mut throttle = read_unlock_throttle(&chain)?;
+    throttle.wipe_after_failed_attempts = max_failed_attempts;
+    write_unlock_throttle(&chain, &throttle)
+}
+
+/// Measures Argon2id on this machine and saves a policy that takes about
+/// `target_ms` to unlock. Existing slots pick it up on their next unlock.
+#[tauri::command]
+pub async fn app_lock_calibrate_kdf(
+    target_ms: Option<u64>,
+    runtime: State<'_, AppLockRuntimeState>,
+    storage: State<'_, SecureStorageState>,
+) -> Result<KdfPolicy, String> {
+    let chain = storage.chain();
+    if read_keyset(&chain)?.is_some() && !runtime_is_unlocked(&runtime)? {
+        return Err("Unlock JournAi before calibrating the app lock.".to_string());
+    }
+
+    let target = target_ms
+        .unwrap_or(KDF_CALIBRATION_DEFAULT_MS)
+        .clamp(KDF_CALIBRATION_MIN_MS, KDF_CALIBRATION_MAX_MS);
+    let policy = tauri::async_runtime::spawn_blocking(move || {
+        KdfPolicy::calibrate(Duration::from_millis(target))
+    })
+    .await
+    .map_err(|e| format!("Calibration task failed: {e}"))??;
+
+    write_kdf_policy(&policy)?;
+    Ok(policy)
+}
 
+/// Records user activity, postponing the idle auto-lock.
+#[tauri::command]
+pub fn app_lock_heartbeat(runtime: State<'_, AppLockRuntimeState>) -> Result<(), String> {
+    touch_idle_timer(&runtime)
+}
+
+/// Sets the idle auto-lock timeout. `None` disables it and `0` locks as soon
+/// as every window loses focus.
+#[tauri::command]
+pub fn app_lock_set_idle_timeout(
+    timeout_seconds: Option<u64>,
+    runtime: State<'_, AppLockRuntimeState>,
+) -> Result<(), String> {
+    runtime
+        .idle
+        .lock()
+        .map_err(|_| "Failed to acquire app lock state".to_string())?
+        .set_timeout(timeout_seconds.map(Duration::from_secs), now_ms());
     Ok(())
 }
 
+#[tauri::command]
+pub async fn app_lock_lock(
+    runtime: State<'_, AppLockRuntimeState>,
+    db_instances: State<'_, DbInstances>,
+) -> Result<(), String> {
+    lock_session(&runtime, &db_instances).await
+}
+
+fn delete_keyset(chain: &SecretChain) -> Result<(), String> {
+    chain.delete(APP_LOCK_KEYSET_STORAGE_KEY)?;
+    delete_keyset_backup(chain)?;
+    delete_legacy_keyset_file()
+}
+
 #[tauri::command]
 pub async fn app_lock_disable(
     passphrase: String,
     runtime: State<'_, AppLockRuntimeState>,
+    storage: State<'_, SecureStorageState>,
+    db_instances: State<'_, DbInstances>,
+    app: tauri::AppHandle,
 ) -> Result<(), String> {
-    let Some(keyset) = read_keyset()? else {
+    let chain = storage.chain();
+    let Some(keyset) = read_keyset(&chain)? else {
         return Ok(());
     };
 
-    let dek = tauri::async_runtime::spawn_blocking(move || unwrap_dek(&keyset, &passphrase))
-        .await
-        .map_err(|e| format!("Disable task failed: {e}"))??;
+    let unwrap = move || unwrap_dek(&keyset, &passphrase);
+    let dek = unwrap_dek_throttled(unwrap, &chain, &runtime, &db_instances, &app)
+        .await?
+        .ok_or_else(|| "Invalid passphrase".to_string())?;
 
-    write_open_dek(&dek)?;
-    delete_keyset()?;
-    set_sqlcipher_session_key(&dek);
+    let protection = write_open_dek(&chain, &dek)?;
+    delete_keyset(&chain)?;
+    delete_unlock_throttle(&chain)?;
+    runtime.session_key.set(&dek);
+    set_open_dek_protection(&runtime, Some(protection))?;
     set_runtime_configured(&runtime, false)?;
     set_runtime_unlocked(&runtime, true)
 }
@@ -608,20 +1210,174 @@ pub async fn app_lock_change_passphrase(
     current_passphrase: String,
     new_passphrase: String,
     runtime: State<'_, AppLockRuntimeState>,
+    storage: State<'_, SecureStorageState>,
+    db_instances: State<'_, DbInstances>,
+    app: tauri::AppHandle,
 ) -> Result<(), String> {
-    let Some(existing_keyset) = read_keyset()? else {
+    let chain = storage.chain();
+    let Some(mut keyset) = read_keyset(&chain)? else {
         return Err("App lock is not enabled".to_string());
     };
 
-    let (new_keyset, dek) = tauri::async_runtime::spawn_blocking(move || {
-        let dk = unwrap_dek(&existing_keyset, &current_passphrase)?;
-        let nk = build_keyset_from_passphrase(&new_passphrase, Some(dk))?;
-        Ok::<_, String>((nk, dk))
+    let existing = keyset.clone();
+    let unwrap = move || unwrap_dek(&existing, &current_passphrase);
+    let dek = unwrap_dek_throttled(unwrap, &chain, &runtime, &db_instances, &app)
+        .await?
+        .ok_or_else(|| "Invalid passphrase".to_string())?;
+
+    let keyset = tauri::async_runtime::spawn_blocking(move || {
+        keyset.put_slot(passphrase_slot(&new_passphrase, &dek)?);
+        Ok::<_, String>(keyset)
     })
     .await
     .map_err(|e| format!("Change passphrase task failed: {e}"))??;
 
-    set_sqlcipher_session_key(&dek);
-    write_keyset(&new_keyset)?;
+    runtime.session_key.set(&dek);
+    write_keyset_retiring_secrets(&chain, &keyset)?;
     set_runtime_unlocked(&runtime, true)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secure_storage::{EncryptedFileBackend, MemoryBackend};
+
+    #[test]
+    fn newest_keyset_wins_over_chain_order() {
+        let copy = |generation| {
+            let mut keyset = Keyset::new();
+            keyset.set_generation(generation);
+            keyset.to_json().unwrap()
+        };
+        let copies = ["{".to_string(), copy(3), copy(4), copy(1)];
+
+        let (index, keyset, upgraded) = newest_keyset(&copies).unwrap().unwrap();
+
+        assert_eq!((index, keyset.generation(), upgraded), (2, 4, false));
+        assert!(newest_keyset(&[]).unwrap().is_none());
+        assert!(newest_keyset(&["{".to_string()]).is_err());
+    }
+
+    #[test]
+    fn throttle_uses_the_copy_with_most_failures() {
+        let copies = [
+            r#"{"failedAttempts":1,"lastFailedAtMs":10,"wipeAfterFailedAttempts":8}"#.to_string(),
+            r#"{"failedAttempts":4,"lastFailedAtMs":20,"wipeAfterFailedAttempts":8}"#.to_string(),
+        ];
+
+        let (throttle, damaged) = UnlockThrottle::from_copies(&copies, 99);
+
+        assert!(!damaged);
+        assert_eq!(throttle.failed_attempts, 4);
+        assert_eq!(throttle.next_attempt_at(), Some(20 + 2 * UNLOCK_BACKOFF_BASE_MS));
+        assert_eq!(throttle.remaining_attempts(), Some(4));
+    }
+
+    #[test]
+    fn damaged_throttle_reads_as_maximum_backoff() {
+        let copies = [
+            r#"{"failedAttempts":1,"lastFailedAtMs":10,"wipeAfterFailedAttempts":20}"#.to_string(),
+            r#"{"failedAttem"#.to_string(),
+        ];
+
+        let (throttle, damaged) = UnlockThrottle::from_copies(&copies, 1_000);
+
+        assert!(damaged);
+        assert_eq!(throttle.next_attempt_at(), Some(1_000 + UNLOCK_BACKOFF_MAX_MS));
+        assert_eq!(throttle.failed_attempts, 1);
+        assert_eq!(throttle.wipe_after_failed_attempts, Some(20));
+        assert_eq!(UnlockThrottle::from_copies(&[], 1_000).0.next_attempt_at(), None);
+    }
+
+    #[test]
+    fn damaged_throttle_does_not_bring_the_wipe_closer() {
+        let copies = [
+            r#"{"failedAttempts":0,"lastFailedAtMs":0,"wipeAfterFailedAttempts":5}"#.to_string(),
+            r#"{"failedAttem"#.to_string(),
+        ];
+
+        let (mut throttle, _) = UnlockThrottle::from_copies(&copies, 1_000);
+        assert_eq!(throttle.remaining_attempts(), Some(5));
+
+        throttle.record_failure(1_000 + UNLOCK_BACKOFF_MAX_MS);
+        assert!(!throttle.should_wipe());
+        assert_eq!(throttle.remaining_attempts(), Some(4));
+
+        throttle.reset();
+        assert_eq!(throttle.next_attempt_at(), None);
+    }
+
+    #[test]
+    fn failed_rekey_puts_back_the_exact_keyset_copies() {
+        let dir = std::env::temp_dir().join("journai-app-lock-rotation");
+        let _ = fs::remove_dir_all(&dir);
+        let chain = SecretChain::new(vec![
+            Box::new(EncryptedFileBackend::open(&dir).unwrap()),
+            Box::new(MemoryBackend::default()),
+        ]);
+        let stored = |generation| {
+            let mut keyset = Keyset::new();
+            keyset.set_generation(generation);
+            keyset.to_json().unwrap()
+        };
+        chain.set(APP_LOCK_KEYSET_STORAGE_KEY, &stored(3)).unwrap();
+        chain.set(APP_LOCK_KEYSET_BACKUP_STORAGE_KEY, &stored(2)).unwrap();
+        let before = (
+            chain.snapshot(APP_LOCK_KEYSET_STORAGE_KEY).unwrap(),
+            chain.snapshot(APP_LOCK_KEYSET_BACKUP_STORAGE_KEY).unwrap(),
+        );
+        let db_path = dir.join(SECURE_DB_FILE_NAME);
+        fs::write(&db_path, "before").unwrap();
+
+        let session_key = SessionKey::new();
+        let (current_dek, new_dek) = ([1u8; KEY_LENGTH], [2u8; KEY_LENGTH]);
+        let rekey = || async {
+            fs::write(&db_path, "half rekeyed").unwrap();
+            Err("disk full".to_string())
+        };
+        let result = tauri::async_runtime::block_on(swap_data_key(
+            &chain,
+            &session_key,
+            &db_path,
+            &current_dek,
+            &new_dek,
+            &RotatedKey::Keyset(Keyset::new()),
+            rekey,
+        ));
+
+        assert_eq!(result, Err("Failed to re-encrypt the secure database: disk full".to_string()));
+        assert_eq!(chain.snapshot(APP_LOCK_KEYSET_STORAGE_KEY).unwrap(), before.0);
+        assert_eq!(chain.snapshot(APP_LOCK_KEYSET_BACKUP_STORAGE_KEY).unwrap(), before.1);
+        assert_eq!(before.0, vec![Some(stored(3)), None]);
+        assert_eq!(fs::read_to_string(&db_path).unwrap(), "before");
+        assert!(session_key.matches(&current_dek));
+    }
+
+    #[test]
+    fn failed_rekey_keeps_the_open_dek() {
+        let dir = std::env::temp_dir().join("journai-app-lock-open-rotation");
+        let _ = fs::remove_dir_all(&dir);
+        let chain = SecretChain::new(vec![
+            Box::new(EncryptedFileBackend::open(&dir).unwrap()),
+            Box::new(MemoryBackend::default()),
+        ]);
+        chain.set(APP_LOCK_OPEN_DEK_STORAGE_KEY, &"01".repeat(KEY_LENGTH)).unwrap();
+        let before = chain.snapshot(APP_LOCK_OPEN_DEK_STORAGE_KEY).unwrap();
+
+        let session_key = SessionKey::new();
+        let (current_dek, new_dek) = ([1u8; KEY_LENGTH], [2u8; KEY_LENGTH]);
+        let result = tauri::async_runtime::block_on(swap_data_key(
+            &chain,
+            &session_key,
+            &dir.join(SECURE_DB_FILE_NAME),
+            &current_dek,
+            &new_dek,
+            &RotatedKey::OpenDek,
+            || async { Err("disk full".to_string()) },
+        ));
+
+        assert!(result.is_err());
+        assert_eq!(chain.snapshot(APP_LOCK_OPEN_DEK_STORAGE_KEY).unwrap(), before);
+        assert!(session_key.matches(&current_dek));
+    }
+}
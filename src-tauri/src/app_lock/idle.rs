@@ -0,0 +1,171 @@
+use std::time::Duration;
+
+use tauri::{AppHandle, Emitter, Manager, RunEvent, Window, WindowEvent};
+use tauri_plugin_sql::DbInstances;
+
+use super::{lock_session, now_ms, AppLockRuntimeState};
+
+pub(super) const APP_LOCK_REQUIRED_EVENT: &str = "app-lock-required";
+const IDLE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+/// How long every window must stay unfocused before a zero timeout locks, so
+/// moving focus between our own windows does not count as backgrounding.
+const BACKGROUND_GRACE_MS: u64 = 1_000;
+
+/// Tracks user activity for the idle auto-lock.
+///
+/// Activity is measured on the wall clock rather than a monotonic one, so time
+/// spent in OS suspend counts towards the timeout and the first check after
+/// resume locks the app.
+pub(super) struct IdleTimer {
+    /// `None` disables the auto-lock; zero locks as soon as the app is
+    /// backgrounded.
+    timeout_ms: Option<u64>,
+    last_activity_ms: u64,
+    focused: bool,
+}
+
+impl IdleTimer {
+    pub(super) fn new(now: u64) -> Self {
+        Self {
+            timeout_ms: None,
+            last_activity_ms: now,
+            focused: true,
+        }
+    }
+
+    pub(super) fn set_timeout(&mut self, timeout: Option<Duration>, now: u64) {
+        self.timeout_ms = timeout.map(|timeout| timeout.as_millis() as u64);
+        self.touch(now);
+    }
+
+    pub(super) fn touch(&mut self, now: u64) {
+        self.last_activity_ms = now;
+    }
+
+    fn set_focused(&mut self, focused: bool, now: u64) {
+        self.focused = focused;
+        self.touch(now);
+    }
+
+    fn expired(&self, now: u64) -> bool {
+        let idle_ms = now.saturating_sub(self.last_activity_ms);
+        match self.timeout_ms {
+            None => false,
+            Some(0) => !self.focused && idle_ms >= BACKGROUND_GRACE_MS,
+            Some(timeout_ms) => idle_ms >= timeout_ms,
+        }
+    }
+}
+
+fn should_lock(runtime: &AppLockRuntimeState) -> bool {
+    let configured = runtime
+        .configured_cache
+        .lock()
+        .map(|guard| *guard == Some(true))
+        .unwrap_or(false);
+    let unlocked = runtime.unlocked.lock().map(|guard| *guard).unwrap_or(false);
+    let expired = runtime
+        .idle
+        .lock()
+        .map(|timer| timer.expired(now_ms()))
+        .unwrap_or(false);
+    configured && unlocked && expired
+}
+
+async fn lock_if_idle(app: &AppHandle) {
+    let runtime = app.state::<AppLockRuntimeState>();
+    if !should_lock(&runtime) {
+        return;
+    }
+    let Some(db_instances) = app.try_state::<DbInstances>() else {
+        return;
+    };
+
+    if lock_session(&runtime, &db_instances).await.is_ok() {
+        let _ = app.emit(APP_LOCK_REQUIRED_EVENT, ());
+    }
+}
+
+/// Starts the background check that locks the app once the idle timeout
+/// expires and notifies every window with `app-lock-required`.
+pub fn spawn_idle_watcher(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut interval = tokio::time::interval(IDLE_CHECK_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            lock_if_idle(&app).await;
+        }
+    });
+}
+
+/// Focus changes count as activity; losing focus starts the countdown for a
+/// zero ("lock when backgrounded") timeout.
+pub fn handle_window_event(window: &Window, event: &WindowEvent) {
+    if let WindowEvent::Focused(focused) = event {
+        if let Ok(mut timer) = window.state::<AppLockRuntimeState>().idle.lock() {
+            timer.set_focused(*focused, now_ms());
+        }
+    }
+}
+
+/// Re-checks the timeout as soon as the event loop resumes (e.g. a mobile app
+/// returning to the foreground) instead of waiting for the next tick.
+pub fn handle_run_event(app: &AppHandle, event: &RunEvent) {
+    if let RunEvent::Resumed = event {
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move { lock_if_idle(&app).await });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timer(timeout_secs: u64) -> IdleTimer {
+        let mut timer = IdleTimer::new(0);
+        timer.set_timeout(Some(Duration::from_secs(timeout_secs)), 10_000);
+        timer
+    }
+
+    #[test]
+    fn locks_once_the_timeout_passes_without_activity() {
+        let mut timer = timer(60);
+        assert!(!timer.expired(69_999));
+        assert!(timer.expired(70_000));
+
+        timer.touch(65_000);
+        assert!(!timer.expired(124_999));
+        assert!(timer.expired(125_000));
+    }
+
+    #[test]
+    fn no_timeout_never_locks() {
+        let mut timer = IdleTimer::new(0);
+        timer.set_focused(false, 0);
+        assert!(!timer.expired(u64::MAX));
+    }
+
+    #[test]
+    fn zero_timeout_locks_when_unfocused_after_the_grace_period() {
+        let mut timer = timer(0);
+        assert!(!timer.expired(1_000_000));
+
+        timer.set_focused(false, 20_000);
+        assert!(!timer.expired(20_000 + BACKGROUND_GRACE_MS - 1));
+        assert!(timer.expired(20_000 + BACKGROUND_GRACE_MS));
+
+        // Focus moving to another of our windows within the grace period.
+        timer.set_focused(false, 30_000);
+        timer.set_focused(true, 30_500);
+        assert!(!timer.expired(40_000));
+    }
+
+    #[test]
+    fn time_spent_suspended_counts_as_idle() {
+        let timer = timer(300);
+        // A clock reading from before the last activity does not underflow.
+        assert!(!timer.expired(0));
+        assert!(timer.expired(10_000 + 8 * 60 * 60 * 1_000));
+    }
+}
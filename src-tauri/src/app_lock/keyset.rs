@@ -0,0 +1,626 @@
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+pub(super) const KEY_LENGTH: usize = 32;
+pub(super) const PASSPHRASE_SLOT_LABEL: &str = "passphrase";
+pub(super) const RECOVERY_SLOT_LABEL: &str = "recovery";
+const KEYSET_VERSION: u8 = 2;
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 12;
+const RECOVERY_KEY_ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+const RECOVERY_KEY_SYMBOLS: usize = 32;
+const RECOVERY_KEY_GROUP_SIZE: usize = 4;
+/// Calibration never asks for more memory than this. Mobile apps are killed
+/// long before desktop ones when an unlock allocates hundreds of MiB.
+#[cfg(any(target_os = "android", target_os = "ios"))]
+const CALIBRATION_MAX_MEMORY_KIB: u32 = 64 * 1024;
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+const CALIBRATION_MAX_MEMORY_KIB: u32 = 512 * 1024;
+const CALIBRATION_MAX_ITERATIONS: u32 = 16;
+
+/// Argon2id cost parameters used when sealing a new slot.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KdfPolicy {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl KdfPolicy {
+    pub(super) fn current() -> Self {
+        Self {
+            memory_kib: platform_kdf_memory_kib(),
+            iterations: 3,
+            parallelism: 1,
+        }
+    }
+
+    fn params(&self) -> Result<Params, String> {
+        Params::new(self.memory_kib, self.iterations, self.parallelism, Some(KEY_LENGTH))
+            .map_err(|e| format!("Invalid Argon2 parameters: {e}"))
+    }
+
+    /// True when every cost parameter is at least as strong as `other`'s.
+    pub(super) fn covers(&self, other: &KdfPolicy) -> bool {
+        self.memory_kib >= other.memory_kib
+            && self.iterations >= other.iterations
+            && self.parallelism >= other.parallelism
+    }
+
+    /// Component-wise maximum, so upgrading a slot never lowers any cost.
+    pub(super) fn strongest(self, other: KdfPolicy) -> Self {
+        Self {
+            memory_kib: self.memory_kib.max(other.memory_kib),
+            iterations: self.iterations.max(other.iterations),
+            parallelism: self.parallelism.max(other.parallelism),
+        }
+    }
+
+    /// Clamps memory and iterations to what calibration may pick on this
+    /// platform, e.g. for a policy saved before the limits were lowered.
+    pub(super) fn capped(self) -> Self {
+        Self {
+            memory_kib: self.memory_kib.min(CALIBRATION_MAX_MEMORY_KIB),
+            iterations: self.iterations.min(CALIBRATION_MAX_ITERATIONS),
+            ..self
+        }
+    }
+
+    /// Picks parameters that take roughly `target` to derive a key on this
+    /// machine. Memory is doubled first, then iterations are scaled to fill the
+    /// remaining budget. The result is never weaker than [`KdfPolicy::current`].
+    pub(super) fn calibrate(target: Duration) -> Result<Self, String> {
+        Self::calibrate_with(target, Self::measure)
+    }
+
+    fn calibrate_with(
+        target: Duration,
+        mut measure: impl FnMut(&Self) -> Result<Duration, String>,
+    ) -> Result<Self, String> {
+        let mut policy = Self::current();
+        loop {
+            let elapsed = measure(&policy)?;
+            if elapsed.saturating_mul(2) > target {
+                return Ok(policy);
+            }
+
+            if policy.memory_kib < CALIBRATION_MAX_MEMORY_KIB {
+                policy.memory_kib = (policy.memory_kib * 2).min(CALIBRATION_MAX_MEMORY_KIB);
+                continue;
+            }
+
+            let scale = target.as_secs_f64() / elapsed.as_secs_f64().max(f64::EPSILON);
+            let iterations = (f64::from(policy.iterations) * scale).floor() as u32;
+            policy.iterations = iterations.clamp(policy.iterations, CALIBRATION_MAX_ITERATIONS);
+            return Ok(policy);
+        }
+    }
+
+    fn measure(&self) -> Result<Duration, String> {
+        let kdf = KdfParams::generate(*self);
+        let started = Instant::now();
+        kdf.derive(b"journai-kdf-calibration")?;
+        Ok(started.elapsed())
+    }
+}
+
+fn platform_kdf_memory_kib() -> u32 {
+    #[cfg(any(target_os = "ios", target_os = "android"))]
+    {
+        32 * 1024
+    }
+
+    #[cfg(not(any(target_os = "ios", target_os = "android")))]
+    {
+        64 * 1024
+    }
+}
+
+/// Argon2id parameters stored with a slot.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct KdfParams {
+    salt_b64: String,
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+}
+
+impl KdfParams {
+    fn generate(policy: KdfPolicy) -> Self {
+        let mut salt = [0u8; SALT_LENGTH];
+        OsRng.fill_bytes(&mut salt);
+        Self {
+            salt_b64: BASE64.encode(salt),
+            memory_kib: policy.memory_kib,
+            iterations: policy.iterations,
+            parallelism: policy.parallelism,
+        }
+    }
+
+    pub(super) fn policy(&self) -> KdfPolicy {
+        KdfPolicy {
+            memory_kib: self.memory_kib,
+            iterations: self.iterations,
+            parallelism: self.parallelism,
+        }
+    }
+
+    fn derive(&self, secret: &[u8]) -> Result<[u8; KEY_LENGTH], String> {
+        let params = self
+            .policy()
+            .params()
+            .map_err(|e| format!("Invalid stored Argon2 params: {e}"))?;
+        let salt = decode_base64::<SALT_LENGTH>(&self.salt_b64, "salt")?;
+
+        let mut key = [0u8; KEY_LENGTH];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(secret, &salt, &mut key)
+            .map_err(|e| format!("Failed to derive key with Argon2id: {e}"))?;
+        Ok(key)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) enum KeySlotKind {
+    Passphrase,
+    RecoveryKey,
+}
+
+impl KeySlotKind {
+    fn rejection(self) -> &'static str {
+        match self {
+            KeySlotKind::Passphrase => "Invalid passphrase",
+            KeySlotKind::RecoveryKey => "Invalid recovery key",
+        }
+    }
+}
+
+/// One independently unlockable copy of the DEK.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct KeySlot {
+    pub label: String,
+    pub kind: KeySlotKind,
+    pub kdf: KdfParams,
+    wrapped_dek_b64: String,
+    nonce_b64: String,
+}
+
+impl KeySlot {
+    /// Wraps `dek` under a KEK stretched from `secret` with Argon2id under
+    /// `policy`.
+    pub(super) fn seal(
+        kind: KeySlotKind,
+        label: &str,
+        secret: &[u8],
+        dek: &[u8; KEY_LENGTH],
+        policy: KdfPolicy,
+    ) -> Result<Self, String> {
+        let kdf = KdfParams::generate(policy);
+        let kek = kdf.derive(secret)?;
+
+        let mut nonce = [0u8; NONCE_LENGTH];
+        OsRng.fill_bytes(&mut nonce);
+
+        let cipher = Aes256Gcm::new_from_slice(&kek)
+            .map_err(|e| format!("Failed to initialize cipher: {e}"))?;
+        let wrapped_dek = cipher
+            .encrypt(Nonce::from_slice(&nonce), dek.as_ref())
+            .map_err(|e| format!("Failed to wrap DEK: {e}"))?;
+
+        Ok(Self {
+            label: label.to_string(),
+            kind,
+            kdf,
+            wrapped_dek_b64: BASE64.encode(wrapped_dek),
+            nonce_b64: BASE64.encode(nonce),
+        })
+    }
+
+    pub(super) fn open(&self, secret: &[u8]) -> Result<[u8; KEY_LENGTH], String> {
+        let kek = self.kdf.derive(secret)?;
+
+        let wrapped_dek = BASE64
+            .decode(&self.wrapped_dek_b64)
+            .map_err(|e| format!("Invalid wrapped DEK encoding: {e}"))?;
+        let nonce = decode_base64::<NONCE_LENGTH>(&self.nonce_b64, "nonce")?;
+
+        let cipher = Aes256Gcm::new_from_slice(&kek)
+            .map_err(|e| format!("Failed to initialize cipher: {e}"))?;
+        let unwrapped = cipher
+            .decrypt(Nonce::from_slice(&nonce), wrapped_dek.as_ref())
+            .map_err(|_| self.kind.rejection().to_string())?;
+
+        if unwrapped.len() != KEY_LENGTH {
+            return Err("Invalid unwrapped DEK length".to_string());
+        }
+
+        let mut dek = [0u8; KEY_LENGTH];
+        dek.copy_from_slice(&unwrapped);
+        Ok(dek)
+    }
+}
+
+/// Versioned app lock keyset. Version 2 holds any number of labelled slots that
+/// each wrap the same DEK.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct Keyset {
+    version: u8,
+    /// Raised on every write, so the newest of several stored copies wins.
+    #[serde(default)]
+    generation: u64,
+    slots: Vec<KeySlot>,
+}
+
+impl Keyset {
+    pub(super) fn new() -> Self {
+        Self {
+            version: KEYSET_VERSION,
+            generation: 0,
+            slots: Vec::new(),
+        }
+    }
+
+    pub(super) fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub(super) fn set_generation(&mut self, generation: u64) {
+        self.generation = generation;
+    }
+
+    /// Parses a stored keyset of any supported version. The flag is set when
+    /// the input was an older format and should be written back.
+    pub(super) fn parse(raw: &str) -> Result<(Self, bool), String> {
+        #[derive(Deserialize)]
+        struct VersionProbe {
+            version: u8,
+        }
+
+        let probe: VersionProbe =
+            serde_json::from_str(raw).map_err(|e| format!("Invalid stored app lock keyset: {e}"))?;
+        match probe.version {
+            1 => {
+                let v1: KeysetV1 = serde_json::from_str(raw)
+                    .map_err(|e| format!("Invalid stored app lock keyset: {e}"))?;
+                Ok((v1.into(), true))
+            }
+            KEYSET_VERSION => {
+                let keyset = serde_json::from_str(raw)
+                    .map_err(|e| format!("Invalid stored app lock keyset: {e}"))?;
+                Ok((keyset, false))
+            }
+            other => Err(format!("Unsupported app lock keyset version {other}")),
+        }
+    }
+
+    pub(super) fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string(self).map_err(|e| format!("Failed to serialize keyset: {e}"))
+    }
+
+    pub(super) fn has_slot_kind(&self, kind: KeySlotKind) -> bool {
+        self.slots.iter().any(|slot| slot.kind == kind)
+    }
+
+    /// Inserts `slot`, replacing any existing slot with the same label.
+    pub(super) fn put_slot(&mut self, slot: KeySlot) {
+        match self.slots.iter_mut().find(|existing| existing.label == slot.label) {
+            Some(existing) => *existing = slot,
+            None => self.slots.push(slot),
+        }
+    }
+
+    pub(super) fn remove_slot(&mut self, label: &str) -> bool {
+        let before = self.slots.len();
+        self.slots.retain(|slot| slot.label != label);
+        self.slots.len() != before
+    }
+
+    /// Re-seals every slot of `kind` that `secret` opens and whose KDF is weaker
+    /// than `policy`. Returns whether any slot changed.
+    pub(super) fn upgrade_slots(
+        &mut self,
+        kind: KeySlotKind,
+        secret: &[u8],
+        dek: &[u8; KEY_LENGTH],
+        policy: KdfPolicy,
+    ) -> Result<bool, String> {
+        let mut upgraded = false;
+        for slot in self.slots.iter_mut().filter(|slot| slot.kind == kind) {
+            let stored = slot.kdf.policy();
+            if stored.covers(&policy) || slot.open(secret).ok().as_ref() != Some(dek) {
+                continue;
+            }
+
+            *slot = KeySlot::seal(kind, &slot.label, secret, dek, stored.strongest(policy))?;
+            upgraded = true;
+        }
+        Ok(upgraded)
+    }
+
+    /// Tries every slot of `kind` with `secret` and returns the first DEK that
+    /// unwraps.
+    pub(super) fn open(&self, kind: KeySlotKind, secret: &[u8]) -> Result<[u8; KEY_LENGTH], String> {
+        let mut last_error = None;
+        for slot in self.slots.iter().filter(|slot| slot.kind == kind) {
+            match slot.open(secret) {
+                Ok(dek) => return Ok(dek),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| kind.rejection().to_string()))
+    }
+}
+
+/// The original single-passphrase format, kept only for upgrading.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KeysetV1 {
+    salt_b64: String,
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+    wrapped_dek_b64: String,
+    nonce_b64: String,
+}
+
+// The v1 `passwordHash` is not carried over: it was an Argon2id output over the
+// same passphrase, salt and parameters as the KEK, so it duplicated the KEK.
+// AES-GCM authentication already rejects a wrong passphrase.
+impl From<KeysetV1> for Keyset {
+    fn from(v1: KeysetV1) -> Self {
+        let mut keyset = Keyset::new();
+        keyset.put_slot(KeySlot {
+            label: PASSPHRASE_SLOT_LABEL.to_string(),
+            kind: KeySlotKind::Passphrase,
+            kdf: KdfParams {
+                salt_b64: v1.salt_b64,
+                memory_kib: v1.memory_kib,
+                iterations: v1.iterations,
+                parallelism: v1.parallelism,
+            },
+            wrapped_dek_b64: v1.wrapped_dek_b64,
+            nonce_b64: v1.nonce_b64,
+        });
+        keyset
+    }
+}
+
+fn decode_base64<const N: usize>(value: &str, label: &str) -> Result<[u8; N], String> {
+    let bytes = BASE64
+        .decode(value)
+        .map_err(|e| format!("Invalid base64 for {label}: {e}"))?;
+    if bytes.len() != N {
+        return Err(format!(
+            "Invalid decoded length for {label}: expected {N}, got {}",
+            bytes.len()
+        ));
+    }
+    let mut output = [0u8; N];
+    output.copy_from_slice(&bytes);
+    Ok(output)
+}
+
+/// Generates a 160-bit recovery key as Crockford base32, grouped for printing
+/// (e.g. `7K3M-QX0D-…`).
+pub(super) fn generate_recovery_key() -> String {
+    let mut output = String::with_capacity(RECOVERY_KEY_SYMBOLS + RECOVERY_KEY_SYMBOLS / RECOVERY_KEY_GROUP_SIZE);
+    for i in 0..RECOVERY_KEY_SYMBOLS {
+        if i > 0 && i % RECOVERY_KEY_GROUP_SIZE == 0 {
+            output.push('-');
+        }
+        let index = (OsRng.next_u32() % RECOVERY_KEY_ALPHABET.len() as u32) as usize;
+        output.push(RECOVERY_KEY_ALPHABET[index] as char);
+    }
+    output
+}
+
+/// Canonicalizes a typed recovery key: drops separators, uppercases, and maps
+/// the Crockford look-alikes (`O` → `0`, `I`/`L` → `1`).
+pub(super) fn normalize_recovery_key(recovery_key: &str) -> Result<String, String> {
+    let normalized: String = recovery_key
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| match c.to_ascii_uppercase() {
+            'O' => '0',
+            'I' | 'L' => '1',
+            other => other,
+        })
+        .collect();
+
+    if normalized.len() != RECOVERY_KEY_SYMBOLS
+        || !normalized.bytes().all(|b| RECOVERY_KEY_ALPHABET.contains(&b))
+    {
+        return Err("Invalid recovery key".to_string());
+    }
+    Ok(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use argon2::password_hash::{PasswordHasher, SaltString};
+
+    const TEST_POLICY: KdfPolicy = KdfPolicy {
+        memory_kib: 64,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    fn test_dek() -> [u8; KEY_LENGTH] {
+        let mut dek = [0u8; KEY_LENGTH];
+        OsRng.fill_bytes(&mut dek);
+        dek
+    }
+
+    /// Builds a keyset JSON exactly as the v1 writer produced it.
+    fn v1_keyset_json(passphrase: &str, dek: &[u8; KEY_LENGTH]) -> String {
+        let kdf = KdfParams::generate(TEST_POLICY);
+        let salt = decode_base64::<SALT_LENGTH>(&kdf.salt_b64, "salt").unwrap();
+        let salt_string = SaltString::encode_b64(&salt).unwrap();
+        let password_hash = Argon2::new(Algorithm::Argon2id, Version::V0x13, TEST_POLICY.params().unwrap())
+            .hash_password(passphrase.as_bytes(), &salt_string)
+            .unwrap()
+            .to_string();
+        let kek = kdf.derive(passphrase.as_bytes()).unwrap();
+        let mut nonce = [0u8; NONCE_LENGTH];
+        OsRng.fill_bytes(&mut nonce);
+        let wrapped = Aes256Gcm::new_from_slice(&kek)
+            .unwrap()
+            .encrypt(Nonce::from_slice(&nonce), dek.as_ref())
+            .unwrap();
+
+        serde_json::json!({
+            "version": 1,
+            "passwordHash": password_hash,
+            "saltB64": kdf.salt_b64,
+            "memoryKib": TEST_POLICY.memory_kib,
+            "iterations": TEST_POLICY.iterations,
+            "parallelism": TEST_POLICY.parallelism,
+            "wrappedDekB64": BASE64.encode(wrapped),
+            "nonceB64": BASE64.encode(nonce),
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn v2_keyset_round_trips_through_json() {
+        let dek = test_dek();
+        let recovery_key = generate_recovery_key();
+        let normalized = normalize_recovery_key(&recovery_key).unwrap();
+        let mut keyset = Keyset::new();
+        keyset.put_slot(
+            KeySlot::seal(KeySlotKind::Passphrase, PASSPHRASE_SLOT_LABEL, b"correct horse", &dek, TEST_POLICY).unwrap(),
+        );
+        keyset.put_slot(
+            KeySlot::seal(KeySlotKind::RecoveryKey, RECOVERY_SLOT_LABEL, normalized.as_bytes(), &dek, TEST_POLICY)
+                .unwrap(),
+        );
+
+        let (parsed, upgraded) = Keyset::parse(&keyset.to_json().unwrap()).unwrap();
+
+        assert!(!upgraded);
+        assert_eq!(parsed, keyset);
+        assert_eq!(parsed.open(KeySlotKind::Passphrase, b"correct horse").unwrap(), dek);
+        assert_eq!(parsed.open(KeySlotKind::RecoveryKey, normalized.as_bytes()).unwrap(), dek);
+    }
+
+    #[test]
+    fn v1_keyset_upgrades_without_losing_the_dek() {
+        let dek = test_dek();
+        let raw = v1_keyset_json("correct horse", &dek);
+
+        let (upgraded, was_upgraded) = Keyset::parse(&raw).unwrap();
+
+        assert!(was_upgraded);
+        assert_eq!(upgraded.version, KEYSET_VERSION);
+        assert_eq!(upgraded.open(KeySlotKind::Passphrase, b"correct horse").unwrap(), dek);
+        assert_eq!(
+            upgraded.open(KeySlotKind::Passphrase, b"wrong horse").unwrap_err(),
+            "Invalid passphrase"
+        );
+        assert_eq!(upgraded.slots[0].label, PASSPHRASE_SLOT_LABEL);
+        assert_eq!(upgraded.slots[0].kdf.policy(), TEST_POLICY);
+
+        let (reparsed, upgraded_again) = Keyset::parse(&upgraded.to_json().unwrap()).unwrap();
+        assert!(!upgraded_again);
+        assert_eq!(reparsed, upgraded);
+        assert!(!upgraded.to_json().unwrap().contains("passwordHash"));
+    }
+
+    #[test]
+    fn put_slot_replaces_by_label() {
+        let dek = test_dek();
+        let mut keyset = Keyset::new();
+        keyset.put_slot(KeySlot::seal(KeySlotKind::Passphrase, PASSPHRASE_SLOT_LABEL, b"first pass", &dek, TEST_POLICY).unwrap());
+        keyset.put_slot(KeySlot::seal(KeySlotKind::Passphrase, PASSPHRASE_SLOT_LABEL, b"second pass", &dek, TEST_POLICY).unwrap());
+
+        assert_eq!(keyset.slots.len(), 1);
+        assert!(keyset.open(KeySlotKind::Passphrase, b"first pass").is_err());
+        assert_eq!(keyset.open(KeySlotKind::Passphrase, b"second pass").unwrap(), dek);
+        assert!(keyset.remove_slot(PASSPHRASE_SLOT_LABEL));
+        assert!(!keyset.has_slot_kind(KeySlotKind::Passphrase));
+    }
+
+    #[test]
+    fn recovery_keys_normalize_typed_variants() {
+        let key = generate_recovery_key();
+        let typed = key.to_lowercase().replace('-', " ").replace('0', "o").replace('1', "l");
+
+        assert_eq!(normalize_recovery_key(&typed).unwrap(), key.replace('-', ""));
+        assert!(normalize_recovery_key("too-short").is_err());
+    }
+
+    #[test]
+    fn weak_slots_are_upgraded_without_changing_the_dek() {
+        let dek = test_dek();
+        let stronger = KdfPolicy {
+            memory_kib: 128,
+            ..TEST_POLICY
+        };
+        let mut keyset = Keyset::new();
+        keyset.put_slot(
+            KeySlot::seal(KeySlotKind::Passphrase, PASSPHRASE_SLOT_LABEL, b"passphrase", &dek, TEST_POLICY).unwrap(),
+        );
+
+        assert!(!keyset
+            .upgrade_slots(KeySlotKind::Passphrase, b"wrong passphrase", &dek, stronger)
+            .unwrap());
+        assert!(keyset
+            .upgrade_slots(KeySlotKind::Passphrase, b"passphrase", &dek, stronger)
+            .unwrap());
+        assert_eq!(keyset.slots[0].kdf.policy(), stronger);
+        assert_eq!(keyset.open(KeySlotKind::Passphrase, b"passphrase").unwrap(), dek);
+        assert!(!keyset
+            .upgrade_slots(KeySlotKind::Passphrase, b"passphrase", &dek, TEST_POLICY)
+            .unwrap());
+    }
+
+    #[test]
+    fn generation_defaults_to_zero_and_round_trips() {
+        let (keyset, upgraded) = Keyset::parse(r#"{"version":2,"slots":[]}"#).unwrap();
+        assert!(!upgraded);
+        assert_eq!(keyset.generation(), 0);
+
+        let mut keyset = Keyset::new();
+        keyset.set_generation(7);
+        assert_eq!(Keyset::parse(&keyset.to_json().unwrap()).unwrap().0.generation(), 7);
+    }
+
+    #[test]
+    fn calibration_stays_within_the_memory_cap() {
+        let mut measured = Vec::new();
+        let policy = KdfPolicy::calibrate_with(Duration::from_secs(60), |policy| {
+            measured.push(policy.memory_kib);
+            Ok(Duration::from_millis(1))
+        })
+        .unwrap();
+
+        assert_eq!(policy.memory_kib, CALIBRATION_MAX_MEMORY_KIB);
+        assert_eq!(policy.iterations, CALIBRATION_MAX_ITERATIONS);
+        assert!(measured.iter().all(|memory_kib| *memory_kib <= CALIBRATION_MAX_MEMORY_KIB));
+        assert!(CALIBRATION_MAX_MEMORY_KIB >= KdfPolicy::current().memory_kib);
+
+        let saved = KdfPolicy {
+            memory_kib: CALIBRATION_MAX_MEMORY_KIB * 4,
+            ..policy
+        };
+        assert_eq!(saved.capped(), policy);
+    }
+
+    #[test]
+    fn unsupported_versions_are_rejected() {
+        assert!(Keyset::parse(r#"{"version":9,"slots":[]}"#).is_err());
+    }
+}
@@ -0,0 +1,122 @@
+use std::fs;
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use super::keyset::KEY_LENGTH;
+use crate::secure_storage::{BackendKind, SecretChain};
+
+pub(super) const APP_LOCK_OPEN_DEK_STORAGE_KEY: &str = "journai.app_lock.open_dek";
+/// Plaintext hex written by older versions.
+const LEGACY_PLAINTEXT_OPEN_DEK_FILE_NAME: &str = "app_lock_open_dek.txt";
+
+/// Where the DEK lives while the app lock is disabled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OpenDekProtection {
+    /// Only in the OS keychain.
+    Keychain,
+    /// In the encrypted file vault, whose key is bound to this machine.
+    MachineBound,
+    /// In the encrypted file vault with no machine binding available; anyone
+    /// who can read the app data directory can recover the DEK.
+    Unprotected,
+}
+
+impl From<BackendKind> for OpenDekProtection {
+    fn from(kind: BackendKind) -> Self {
+        match kind {
+            BackendKind::Keychain => OpenDekProtection::Keychain,
+            BackendKind::MachineBoundFile => OpenDekProtection::MachineBound,
+            BackendKind::File | BackendKind::Memory => OpenDekProtection::Unprotected,
+        }
+    }
+}
+
+fn open_dek_dir() -> Result<PathBuf, String> {
+    let mut base = dirs::data_local_dir()
+        .or_else(dirs::data_dir)
+        .ok_or_else(|| "Unable to resolve app data directory for open DEK".to_string())?;
+    base.push("journai");
+    Ok(base)
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    let mut output = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        output.push_str(&format!("{byte:02x}"));
+    }
+    output
+}
+
+fn decode_hex_key(hex: &str) -> Option<[u8; KEY_LENGTH]> {
+    let trimmed = hex.trim();
+    if trimmed.len() != KEY_LENGTH * 2 || !trimmed.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let mut key = [0u8; KEY_LENGTH];
+    for i in 0..KEY_LENGTH {
+        key[i] = u8::from_str_radix(&trimmed[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(key)
+}
+
+fn read_legacy_plaintext_open_dek_file() -> Result<Option<[u8; KEY_LENGTH]>, String> {
+    let path = open_dek_dir()?.join(LEGACY_PLAINTEXT_OPEN_DEK_FILE_NAME);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let hex = fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read open DEK {}: {e}", path.display()))?;
+    Ok(decode_hex_key(&hex))
+}
+
+fn delete_legacy_open_dek_file() -> Result<(), String> {
+    let path = open_dek_dir()?.join(LEGACY_PLAINTEXT_OPEN_DEK_FILE_NAME);
+    if path.exists() {
+        fs::remove_file(&path)
+            .map_err(|e| format!("Failed to remove open DEK {}: {e}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Stores the DEK used while the app lock is disabled in the secret chain:
+/// the keychain when it works, otherwise the encrypted file vault. Refuses to
+/// keep it only in memory, since the database would be unreadable after a
+/// restart.
+pub(super) fn write_open_dek(chain: &SecretChain, dek: &[u8; KEY_LENGTH]) -> Result<OpenDekProtection, String> {
+    let kind = chain.set(APP_LOCK_OPEN_DEK_STORAGE_KEY, &encode_hex(dek))?;
+    if !kind.persists() {
+        let _ = chain.delete(APP_LOCK_OPEN_DEK_STORAGE_KEY);
+        return Err("No persistent secure storage is available for the database key".to_string());
+    }
+
+    delete_legacy_open_dek_file()?;
+    Ok(kind.into())
+}
+
+/// Reads the open DEK. A DEK found further down the chain is moved to its
+/// primary backend (e.g. into the keychain once it has become available), and
+/// the plaintext file left by earlier versions is migrated into the chain.
+pub(super) fn read_open_dek(chain: &SecretChain) -> Result<Option<([u8; KEY_LENGTH], OpenDekProtection)>, String> {
+    if let Some((hex, kind)) = chain.locate(APP_LOCK_OPEN_DEK_STORAGE_KEY)? {
+        if let Some(dek) = decode_hex_key(&hex) {
+            if chain.primary_kind() == Some(kind) {
+                return Ok(Some((dek, kind.into())));
+            }
+            return write_open_dek(chain, &dek).map(|protection| Some((dek, protection)));
+        }
+    }
+
+    match read_legacy_plaintext_open_dek_file()? {
+        Some(dek) => write_open_dek(chain, &dek).map(|protection| Some((dek, protection))),
+        None => Ok(None),
+    }
+}
+
+pub(super) fn delete_open_dek(chain: &SecretChain) -> Result<(), String> {
+    let _ = chain.delete(APP_LOCK_OPEN_DEK_STORAGE_KEY);
+    delete_legacy_open_dek_file()
+}
@@ -2,15 +2,16 @@
 use tauri::Manager;
 #[cfg(desktop)]
 use tauri::Emitter;
-use tauri_plugin_sql::{Migration, MigrationKind};
+use tauri_plugin_sql::{Migration, MigrationKind, SessionKey};
 
 #[cfg(target_os = "ios")]
 mod ios_webview;
 
 mod app_lock;
+mod private_file;
 mod secure_storage;
 
-const SECURE_DB_URL: &str = "sqlite:journai.db";
+pub(crate) const SECURE_DB_URL: &str = "sqlite:journai.db";
 
 #[tauri::command]
 fn greet(name: &str) -> String {
@@ -30,9 +31,16 @@ pub fn run() {
                 created_at TEXT NOT NULL,
                 updated_at TEXT NOT NULL
             );
-            CREATE INDEX IF NOT EXISTS idx_entries_date ON entries(date DESC);",
+            CREATE INDEX IF NOT EXISTS idx_entries_date ON entries(date DESC);"
+            .into(),
             kind: MigrationKind::Up,
         },
+        Migration {
+            version: 1,
+            description: "create_entries_table",
+            sql: "DROP TABLE IF EXISTS entries;".into(),
+            kind: MigrationKind::Down,
+        },
         Migration {
             version: 2,
             description: "create_todos_table",
@@ -45,9 +53,16 @@ pub fn run() {
                 created_at TEXT NOT NULL,
                 updated_at TEXT NOT NULL
             );
-            CREATE INDEX IF NOT EXISTS idx_todos_date ON todos(date);",
+            CREATE INDEX IF NOT EXISTS idx_todos_date ON todos(date);"
+            .into(),
             kind: MigrationKind::Up,
         },
+        Migration {
+            version: 2,
+            description: "create_todos_table",
+            sql: "DROP TABLE IF EXISTS todos;".into(),
+            kind: MigrationKind::Down,
+        },
         Migration {
             version: 3,
             description: "create_sticky_notes_table",
@@ -58,9 +73,16 @@ pub fn run() {
                 created_at TEXT NOT NULL,
                 updated_at TEXT NOT NULL
             );
-            CREATE INDEX IF NOT EXISTS idx_sticky_notes_date ON sticky_notes(date);",
+            CREATE INDEX IF NOT EXISTS idx_sticky_notes_date ON sticky_notes(date);"
+            .into(),
             kind: MigrationKind::Up,
         },
+        Migration {
+            version: 3,
+            description: "create_sticky_notes_table",
+            sql: "DROP TABLE IF EXISTS sticky_notes;".into(),
+            kind: MigrationKind::Down,
+        },
         Migration {
             version: 4,
             description: "add_position_to_todos",
@@ -68,9 +90,16 @@ pub fn run() {
             UPDATE todos SET position = (
                 SELECT COUNT(*) FROM todos t2
                 WHERE t2.date = todos.date AND t2.created_at <= todos.created_at
-            ) - 1;",
+            ) - 1;"
+            .into(),
             kind: MigrationKind::Up,
         },
+        Migration {
+            version: 4,
+            description: "add_position_to_todos",
+            sql: "ALTER TABLE todos DROP COLUMN position;".into(),
+            kind: MigrationKind::Down,
+        },
         Migration {
             version: 5,
             description: "create_chats_tables",
@@ -90,9 +119,18 @@ pub fn run() {
                 created_at TEXT NOT NULL,
                 FOREIGN KEY (chat_id) REFERENCES chats(id) ON DELETE CASCADE
             );
-            CREATE INDEX IF NOT EXISTS idx_chat_messages_chat_id ON chat_messages(chat_id);",
+            CREATE INDEX IF NOT EXISTS idx_chat_messages_chat_id ON chat_messages(chat_id);"
+            .into(),
             kind: MigrationKind::Up,
         },
+        Migration {
+            version: 5,
+            description: "create_chats_tables",
+            sql: "DROP TABLE IF EXISTS chat_messages;
+            DROP TABLE IF EXISTS chats;"
+            .into(),
+            kind: MigrationKind::Down,
+        },
         Migration {
             version: 6,
             description: "create_entries_fts",
@@ -115,9 +153,20 @@ pub fn run() {
                 INSERT INTO entries_fts(rowid, content) VALUES (NEW.rowid, NEW.content);
             END;
 
-            INSERT INTO entries_fts(rowid, content) SELECT rowid, content FROM entries;",
+            INSERT INTO entries_fts(rowid, content) SELECT rowid, content FROM entries;"
+            .into(),
             kind: MigrationKind::Up,
         },
+        Migration {
+            version: 6,
+            description: "create_entries_fts",
+            sql: "DROP TRIGGER IF EXISTS entries_au;
+            DROP TRIGGER IF EXISTS entries_ad;
+            DROP TRIGGER IF EXISTS entries_ai;
+            DROP TABLE IF EXISTS entries_fts;"
+            .into(),
+            kind: MigrationKind::Down,
+        },
         Migration {
             version: 7,
             description: "create_embeddings_table",
@@ -132,9 +181,16 @@ pub fn run() {
                 FOREIGN KEY (entry_id) REFERENCES entries(id) ON DELETE CASCADE
             );
             CREATE INDEX IF NOT EXISTS idx_embedding_chunks_entry ON embedding_chunks(entry_id);
-            CREATE INDEX IF NOT EXISTS idx_embedding_chunks_date ON embedding_chunks(entry_date);",
+            CREATE INDEX IF NOT EXISTS idx_embedding_chunks_date ON embedding_chunks(entry_date);"
+            .into(),
             kind: MigrationKind::Up,
         },
+        Migration {
+            version: 7,
+            description: "create_embeddings_table",
+            sql: "DROP TABLE IF EXISTS embedding_chunks;".into(),
+            kind: MigrationKind::Down,
+        },
         Migration {
             version: 8,
             description: "create_entities_tables",
@@ -162,21 +218,42 @@ pub fn run() {
                 FOREIGN KEY (entry_id) REFERENCES entries(id) ON DELETE CASCADE
             );
             CREATE INDEX IF NOT EXISTS idx_entity_mentions_entity ON entity_mentions(entity_id);
-            CREATE INDEX IF NOT EXISTS idx_entity_mentions_entry ON entity_mentions(entry_id);",
+            CREATE INDEX IF NOT EXISTS idx_entity_mentions_entry ON entity_mentions(entry_id);"
+            .into(),
             kind: MigrationKind::Up,
         },
+        Migration {
+            version: 8,
+            description: "create_entities_tables",
+            sql: "DROP TABLE IF EXISTS entity_mentions;
+            DROP TABLE IF EXISTS entities;"
+            .into(),
+            kind: MigrationKind::Down,
+        },
         Migration {
             version: 9,
             description: "add_citations_to_chat_messages",
-            sql: "ALTER TABLE chat_messages ADD COLUMN citations TEXT;",
+            sql: "ALTER TABLE chat_messages ADD COLUMN citations TEXT;".into(),
             kind: MigrationKind::Up,
         },
+        Migration {
+            version: 9,
+            description: "add_citations_to_chat_messages",
+            sql: "ALTER TABLE chat_messages DROP COLUMN citations;".into(),
+            kind: MigrationKind::Down,
+        },
         Migration {
             version: 10,
             description: "add_rag_context_to_chat_messages",
-            sql: "ALTER TABLE chat_messages ADD COLUMN rag_context TEXT;",
+            sql: "ALTER TABLE chat_messages ADD COLUMN rag_context TEXT;".into(),
             kind: MigrationKind::Up,
         },
+        Migration {
+            version: 10,
+            description: "add_rag_context_to_chat_messages",
+            sql: "ALTER TABLE chat_messages DROP COLUMN rag_context;".into(),
+            kind: MigrationKind::Down,
+        },
         Migration {
             version: 11,
             description: "create_analytics_tables",
@@ -204,9 +281,18 @@ pub fn run() {
                 updated_at TEXT NOT NULL,
                 FOREIGN KEY (entry_id) REFERENCES entries(id) ON DELETE CASCADE
             );
-            CREATE INDEX IF NOT EXISTS idx_queue_status ON analytics_queue(status);",
+            CREATE INDEX IF NOT EXISTS idx_queue_status ON analytics_queue(status);"
+            .into(),
             kind: MigrationKind::Up,
         },
+        Migration {
+            version: 11,
+            description: "create_analytics_tables",
+            sql: "DROP TABLE IF EXISTS analytics_queue;
+            DROP TABLE IF EXISTS journal_insights;"
+            .into(),
+            kind: MigrationKind::Down,
+        },
         Migration {
             version: 12,
             description: "create_deep_insights_table",
@@ -214,44 +300,92 @@ pub fn run() {
                 id TEXT PRIMARY KEY,
                 data TEXT NOT NULL,
                 created_at TEXT NOT NULL
-            );",
+            );"
+            .into(),
             kind: MigrationKind::Up,
         },
+        Migration {
+            version: 12,
+            description: "create_deep_insights_table",
+            sql: "DROP TABLE IF EXISTS deep_insights;".into(),
+            kind: MigrationKind::Down,
+        },
         Migration {
             version: 13,
             description: "add_tool_calls_to_chat_messages",
-            sql: "ALTER TABLE chat_messages ADD COLUMN tool_calls TEXT;",
+            sql: "ALTER TABLE chat_messages ADD COLUMN tool_calls TEXT;".into(),
             kind: MigrationKind::Up,
         },
+        Migration {
+            version: 13,
+            description: "add_tool_calls_to_chat_messages",
+            sql: "ALTER TABLE chat_messages DROP COLUMN tool_calls;".into(),
+            kind: MigrationKind::Down,
+        },
         Migration {
             version: 14,
             description: "add_last_content_update_to_entries",
-            sql: "ALTER TABLE entries ADD COLUMN last_content_update TEXT;",
+            sql: "ALTER TABLE entries ADD COLUMN last_content_update TEXT;".into(),
             kind: MigrationKind::Up,
         },
+        Migration {
+            version: 14,
+            description: "add_last_content_update_to_entries",
+            sql: "ALTER TABLE entries DROP COLUMN last_content_update;".into(),
+            kind: MigrationKind::Down,
+        },
         Migration {
             version: 15,
             description: "add_analytics_indices",
             sql: "CREATE INDEX IF NOT EXISTS idx_analytics_queue_entry_id ON analytics_queue(entry_id);
             CREATE INDEX IF NOT EXISTS idx_analytics_queue_status ON analytics_queue(status);
-            CREATE INDEX IF NOT EXISTS idx_journal_insights_entry_id ON journal_insights(entry_id);",
+            CREATE INDEX IF NOT EXISTS idx_journal_insights_entry_id ON journal_insights(entry_id);"
+            .into(),
             kind: MigrationKind::Up,
         },
+        Migration {
+            version: 15,
+            description: "add_analytics_indices",
+            sql: "DROP INDEX IF EXISTS idx_journal_insights_entry_id;
+            DROP INDEX IF EXISTS idx_analytics_queue_status;
+            DROP INDEX IF EXISTS idx_analytics_queue_entry_id;"
+            .into(),
+            kind: MigrationKind::Down,
+        },
         Migration {
             version: 16,
             description: "add_processing_status_to_entries",
             sql: "ALTER TABLE entries ADD COLUMN processed_at TEXT;
-            ALTER TABLE entries ADD COLUMN content_hash TEXT;",
+            ALTER TABLE entries ADD COLUMN content_hash TEXT;"
+            .into(),
             kind: MigrationKind::Up,
         },
+        Migration {
+            version: 16,
+            description: "add_processing_status_to_entries",
+            sql: "ALTER TABLE entries DROP COLUMN content_hash;
+            ALTER TABLE entries DROP COLUMN processed_at;"
+            .into(),
+            kind: MigrationKind::Down,
+        },
         Migration {
             version: 17,
             description: "add_source_location_to_insights",
             sql: "ALTER TABLE journal_insights ADD COLUMN source_text TEXT;
             ALTER TABLE journal_insights ADD COLUMN source_start INTEGER;
-            ALTER TABLE journal_insights ADD COLUMN source_end INTEGER;",
+            ALTER TABLE journal_insights ADD COLUMN source_end INTEGER;"
+            .into(),
             kind: MigrationKind::Up,
         },
+        Migration {
+            version: 17,
+            description: "add_source_location_to_insights",
+            sql: "ALTER TABLE journal_insights DROP COLUMN source_end;
+            ALTER TABLE journal_insights DROP COLUMN source_start;
+            ALTER TABLE journal_insights DROP COLUMN source_text;"
+            .into(),
+            kind: MigrationKind::Down,
+        },
         Migration {
             version: 18,
             description: "enforce_non_empty_sticky_notes",
@@ -271,11 +405,39 @@ pub fn run() {
             WHEN TRIM(COALESCE(NEW.content, '')) = ''
             BEGIN
                 SELECT RAISE(ABORT, 'sticky_notes.content cannot be empty');
-            END;",
+            END;"
+            .into(),
+            kind: MigrationKind::Up,
+        },
+        Migration {
+            version: 18,
+            description: "enforce_non_empty_sticky_notes",
+            sql: "DROP TRIGGER IF EXISTS sticky_notes_validate_content_update;
+            DROP TRIGGER IF EXISTS sticky_notes_validate_content_insert;"
+            .into(),
+            kind: MigrationKind::Down,
+        },
+        Migration {
+            version: 19,
+            description: "drop_text_embeddings",
+            sql: "UPDATE entries SET processed_at = NULL
+            WHERE id IN (SELECT entry_id FROM embedding_chunks WHERE typeof(embedding) <> 'blob');
+
+            DELETE FROM embedding_chunks
+            WHERE entry_id IN (SELECT entry_id FROM embedding_chunks WHERE typeof(embedding) <> 'blob');"
+            .into(),
             kind: MigrationKind::Up,
-        }
+        },
+        Migration {
+            version: 19,
+            description: "drop_text_embeddings",
+            sql: "SELECT 1;".into(),
+            kind: MigrationKind::Down,
+        },
     ];
 
+    let session_key = SessionKey::new();
+
     #[allow(unused_mut)]
     let mut builder = tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
@@ -285,6 +447,7 @@ pub fn run() {
         .plugin(
             tauri_plugin_sql::Builder::default()
                 .add_migrations(SECURE_DB_URL, migrations)
+                .session_key(session_key.clone())
                 .build(),
         );
 
@@ -294,8 +457,12 @@ pub fn run() {
     }
 
     builder
-        .manage(app_lock::AppLockRuntimeState::default())
+        .manage(app_lock::AppLockRuntimeState::new(session_key))
+        .manage(secure_storage::SecureStorageState::default())
         .setup(|app| {
+            secure_storage::init(app.handle());
+            app_lock::spawn_idle_watcher(app.handle().clone());
+
             #[cfg(target_os = "ios")]
             {
                 if let Some(webview_window) = app.get_webview_window("main") {
@@ -372,20 +539,31 @@ pub fn run() {
 
             Ok(())
         })
+        .on_window_event(app_lock::handle_window_event)
         .invoke_handler(tauri::generate_handler![
             greet,
             app_lock::app_lock_status,
             app_lock::app_lock_configure,
             app_lock::app_lock_unlock,
             app_lock::app_lock_lock,
+            app_lock::app_lock_heartbeat,
+            app_lock::app_lock_set_idle_timeout,
             app_lock::app_lock_disable,
             app_lock::app_lock_change_passphrase,
+            app_lock::app_lock_set_wipe_policy,
+            app_lock::app_lock_calibrate_kdf,
+            app_lock::app_lock_rotate_data_key,
+            app_lock::app_lock_unlock_with_recovery,
+            app_lock::app_lock_regenerate_recovery_key,
+            app_lock::app_lock_revoke_recovery_key,
             app_lock::app_lock_backup_and_reset_secure_db,
             secure_storage::secure_storage_set,
             secure_storage::secure_storage_get,
             secure_storage::secure_storage_delete,
-            secure_storage::secure_storage_is_available
+            secure_storage::secure_storage_list,
+            secure_storage::secure_storage_status
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| app_lock::handle_run_event(app, &event));
 }
@@ -0,0 +1,44 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Atomically replaces `path` with `contents`: the data is written and synced to
+/// a temporary sibling that is then renamed over the target. On Unix the file
+/// is created owner-only (0600).
+pub(crate) fn write_private_file(path: &Path, contents: &[u8]) -> Result<(), String> {
+    let mut temp_path = path.as_os_str().to_os_string();
+    temp_path.push(".tmp");
+    let temp_path = PathBuf::from(temp_path);
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let written = options.open(&temp_path).and_then(|mut file| {
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(fs::Permissions::from_mode(0o600))?;
+        }
+        file.write_all(contents)?;
+        file.sync_all()
+    });
+    if let Err(e) = written {
+        let _ = fs::remove_file(&temp_path);
+        return Err(format!("Failed to write {}: {e}", temp_path.display()));
+    }
+
+    fs::rename(&temp_path, path).map_err(|e| {
+        let _ = fs::remove_file(&temp_path);
+        format!("Failed to replace {}: {e}", path.display())
+    })?;
+
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        let _ = fs::File::open(parent).and_then(|dir| dir.sync_all());
+    }
+    Ok(())
+}
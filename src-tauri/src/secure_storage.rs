@@ -1,112 +1,639 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use tauri::Manager;
+
+mod encrypted_file;
+#[cfg(not(target_os = "android"))]
+mod keyring_backend;
+mod memory;
+
+pub use encrypted_file::EncryptedFileBackend;
+#[cfg(not(target_os = "android"))]
+pub use keyring_backend::KeyringBackend;
+pub use memory::MemoryBackend;
+
 const SERVICE_NAME: &str = "com.journai.app";
 const AVAILABILITY_PROBE_KEY: &str = "__journai.secure_storage.availability_probe__";
 const AVAILABILITY_PROBE_VALUE: &str = "probe";
+const UNSUPPORTED_ERROR: &str = "Secure storage is not supported on this device";
+const NOT_PERSISTENT_ERROR: &str =
+    "No persistent secure storage is available; the secret would be lost when JournAi restarts";
+/// Keys the backend keeps for itself (app lock keyset, open DEK, probes); the
+/// webview can never reach them, whatever the allowlist says.
+const RESERVED_KEY_PREFIXES: &[&str] = &["journai.app_lock.", "__journai."];
 
-#[cfg(not(target_os = "android"))]
-mod desktop {
-    use super::SERVICE_NAME;
-    use keyring::Entry;
+/// A secret the webview may read and write through the `secure_storage_*`
+/// commands.
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AllowedSecret {
+    pub key: &'static str,
+    pub label: &'static str,
+    pub description: &'static str,
+}
+
+const ALLOWED_SECRETS: &[AllowedSecret] = &[AllowedSecret {
+    key: "journai.apiKey",
+    label: "OpenAI API key",
+    description: "Used for chat, embeddings, search and entry analysis.",
+}];
+
+/// An allowed secret and whether a value is currently stored for it.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SecretListEntry {
+    #[serde(flatten)]
+    pub secret: AllowedSecret,
+    pub present: bool,
+}
+
+/// Where a backend keeps its secrets, from most to least protected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BackendKind {
+    /// An OS keychain (Keychain, Credential Manager or the Secret Service).
+    Keychain,
+    /// The encrypted file vault with its key bound to this machine.
+    MachineBoundFile,
+    /// The encrypted file vault with its key stored beside it.
+    File,
+    /// Process memory; nothing survives a restart.
+    Memory,
+}
+
+impl BackendKind {
+    pub fn persists(self) -> bool {
+        self != BackendKind::Memory
+    }
+}
+
+/// A store for small string secrets.
+pub trait SecretBackend: Send + Sync {
+    fn kind(&self) -> BackendKind;
+    fn set(&self, key: &str, value: &str) -> Result<(), String>;
+    fn get(&self, key: &str) -> Result<Option<String>, String>;
+    fn delete(&self, key: &str) -> Result<(), String>;
+}
+
+impl<T: SecretBackend + ?Sized> SecretBackend for &'static T {
+    fn kind(&self) -> BackendKind {
+        (**self).kind()
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<(), String> {
+        (**self).set(key, value)
+    }
 
-    pub fn set_secret(key: &str, value: &str) -> Result<(), String> {
-        let entry = Entry::new(SERVICE_NAME, key).map_err(|e| e.to_string())?;
-        entry.set_password(value).map_err(|e| e.to_string())
+    fn get(&self, key: &str) -> Result<Option<String>, String> {
+        (**self).get(key)
     }
 
-    pub fn get_secret(key: &str) -> Result<Option<String>, String> {
-        let entry = Entry::new(SERVICE_NAME, key).map_err(|e| e.to_string())?;
-        match entry.get_password() {
-            Ok(password) => Ok(Some(password)),
-            Err(keyring::Error::NoEntry) => Ok(None),
-            Err(e) => Err(e.to_string()),
+    fn delete(&self, key: &str) -> Result<(), String> {
+        (**self).delete(key)
+    }
+}
+
+/// Backends tried in order: a secret is written to the first one that accepts
+/// it and read from the first one that has it.
+pub struct SecretChain {
+    backends: Vec<Box<dyn SecretBackend>>,
+    probed_at_ms: u64,
+    last_error: Mutex<Option<String>>,
+}
+
+impl SecretChain {
+    pub fn new(backends: Vec<Box<dyn SecretBackend>>) -> Self {
+        Self {
+            backends,
+            probed_at_ms: now_ms(),
+            last_error: Mutex::new(None),
         }
     }
 
-    pub fn delete_secret(key: &str) -> Result<(), String> {
-        let entry = Entry::new(SERVICE_NAME, key).map_err(|e| e.to_string())?;
-        match entry.delete_credential() {
-            Ok(()) => Ok(()),
-            Err(keyring::Error::NoEntry) => Ok(()),
-            Err(e) => Err(e.to_string()),
+    /// The keychain and the encrypted file vault, each only if it passes the
+    /// probe, followed by process memory. The memory backend is shared by
+    /// every chain so a re-probe does not drop what it holds.
+    fn detect() -> Self {
+        let mut backends: Vec<Box<dyn SecretBackend>> = Vec::new();
+        let mut last_error = None;
+
+        #[cfg(not(target_os = "android"))]
+        match probe(&KeyringBackend) {
+            Ok(()) => backends.push(Box::new(KeyringBackend)),
+            Err(e) => last_error = Some(format!("Keychain unavailable: {e}")),
+        }
+
+        let vault = vault_dir()
+            .ok_or_else(|| "Unable to resolve app data directory".to_string())
+            .and_then(|dir| EncryptedFileBackend::open(&dir))
+            .and_then(|vault| probe(&vault).map(|()| vault));
+        match vault {
+            Ok(vault) => backends.push(Box::new(vault)),
+            Err(e) => last_error = Some(format!("Encrypted file vault unavailable: {e}")),
         }
+
+        static PROCESS_MEMORY: OnceLock<MemoryBackend> = OnceLock::new();
+        backends.push(Box::new(PROCESS_MEMORY.get_or_init(MemoryBackend::default)));
+
+        let chain = Self::new(backends);
+        if let Some(e) = last_error {
+            chain.record_error(e);
+        }
+        chain
+    }
+
+    pub(crate) fn record_error(&self, error: String) {
+        if let Ok(mut last_error) = self.last_error.lock() {
+            *last_error = Some(error);
+        }
+    }
+
+    /// The most recent probe or backend failure, even if another backend
+    /// took over.
+    pub fn last_error(&self) -> Option<String> {
+        self.last_error.lock().ok().and_then(|last_error| last_error.clone())
+    }
+
+    /// The backend new secrets go to.
+    pub fn primary_kind(&self) -> Option<BackendKind> {
+        self.backends.first().map(|backend| backend.kind())
+    }
+
+    /// Stores `value` in the first backend that accepts it and removes copies
+    /// from the others, so a stale value cannot shadow it.
+    pub fn set(&self, key: &str, value: &str) -> Result<BackendKind, String> {
+        let mut last_error = None;
+        for (index, backend) in self.backends.iter().enumerate() {
+            match backend.set(key, value) {
+                Ok(()) => {
+                    for (other, stale) in self.backends.iter().enumerate() {
+                        if other != index {
+                            let _ = stale.delete(key);
+                        }
+                    }
+                    return Ok(backend.kind());
+                }
+                Err(e) => {
+                    self.record_error(e.clone());
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| UNSUPPORTED_ERROR.to_string()))
+    }
+
+    /// Like [`set`](Self::set), but fails when only a backend that does not
+    /// persist accepted the value, which is then removed again.
+    pub fn set_persistent(&self, key: &str, value: &str) -> Result<BackendKind, String> {
+        let kind = self.set(key, value)?;
+        if !kind.persists() {
+            let _ = self.delete(key);
+            return Err(NOT_PERSISTENT_ERROR.to_string());
+        }
+        Ok(kind)
+    }
+
+    /// Stores `value` in every backend, for data that must survive one of them
+    /// losing it. Returns the best backend that accepted it.
+    ///
+    /// Fails unless every persistent backend accepted the value, and at least
+    /// one did; the backends already written then get their previous value
+    /// back, so no copy is left newer than the others.
+    pub fn mirror(&self, key: &str, value: &str) -> Result<BackendKind, String> {
+        let mut written = Vec::new();
+        let mut failure = None;
+        for backend in &self.backends {
+            let previous = backend.get(key);
+            match backend.set(key, value) {
+                Ok(()) => written.push((backend, previous)),
+                Err(e) => {
+                    self.record_error(e.clone());
+                    if backend.kind().persists() {
+                        failure = Some(e);
+                        break;
+                    }
+                }
+            }
+        }
+        if failure.is_none() && !written.iter().any(|(backend, _)| backend.kind().persists()) {
+            failure = Some(if written.is_empty() {
+                UNSUPPORTED_ERROR.to_string()
+            } else {
+                NOT_PERSISTENT_ERROR.to_string()
+            });
+        }
+
+        match failure {
+            None => Ok(written[0].0.kind()),
+            Some(e) => {
+                for (backend, previous) in written {
+                    let _ = match previous {
+                        Ok(Some(previous)) => backend.set(key, &previous),
+                        Ok(None) => backend.delete(key),
+                        Err(_) => Ok(()),
+                    };
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Returns the secret and the backend holding it. Backends that fail are
+    /// skipped; the error is only returned if none could be read.
+    pub fn locate(&self, key: &str) -> Result<Option<(String, BackendKind)>, String> {
+        let mut last_error = None;
+        let mut any_readable = false;
+        for backend in &self.backends {
+            match backend.get(key) {
+                Ok(Some(value)) => return Ok(Some((value, backend.kind()))),
+                Ok(None) => any_readable = true,
+                Err(e) => {
+                    self.record_error(e.clone());
+                    last_error = Some(e);
+                }
+            }
+        }
+        if any_readable {
+            return Ok(None);
+        }
+        Err(last_error.unwrap_or_else(|| UNSUPPORTED_ERROR.to_string()))
+    }
+
+    pub fn get(&self, key: &str) -> Result<Option<String>, String> {
+        Ok(self.locate(key)?.map(|(value, _)| value))
+    }
+
+    /// Every stored copy of a secret, in chain order.
+    pub fn copies(&self, key: &str) -> Vec<String> {
+        self.backends
+            .iter()
+            .filter_map(|backend| backend.get(key).ok().flatten())
+            .collect()
+    }
+
+    /// Each backend's copy of a secret, in chain order, for putting back with
+    /// [`restore`](Self::restore). Fails if any backend cannot be read.
+    pub fn snapshot(&self, key: &str) -> Result<Vec<Option<String>>, String> {
+        self.backends.iter().map(|backend| backend.get(key)).collect()
+    }
+
+    /// Puts every backend's copy of a secret back as
+    /// [`snapshot`](Self::snapshot) found it, including the absent ones.
+    pub fn restore(&self, key: &str, snapshot: &[Option<String>]) -> Result<(), String> {
+        let mut result = Ok(());
+        for (backend, value) in self.backends.iter().zip(snapshot) {
+            let restored = match value {
+                Some(value) => backend.set(key, value),
+                None => backend.delete(key),
+            };
+            if let Err(e) = restored {
+                self.record_error(e.clone());
+                result = Err(e);
+            }
+        }
+        result
+    }
+
+    /// Removes the secret from every backend, failing if any copy remains.
+    pub fn delete(&self, key: &str) -> Result<(), String> {
+        if self.backends.is_empty() {
+            return Err(UNSUPPORTED_ERROR.to_string());
+        }
+        let mut result = Ok(());
+        for backend in &self.backends {
+            if let Err(e) = backend.delete(key) {
+                self.record_error(e.clone());
+                result = Err(e);
+            }
+        }
+        result
     }
 }
 
-#[cfg(target_os = "android")]
-mod mobile {
-    pub fn set_secret(_key: &str, _value: &str) -> Result<(), String> {
-        Ok(())
+/// What the webview is told about the secret chain.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SecureStorageStatus {
+    /// The backend new secrets go to.
+    pub backend: Option<BackendKind>,
+    pub persists_across_reboots: bool,
+    pub last_error: Option<String>,
+    /// When the backends were probed, in milliseconds since the Unix epoch.
+    pub checked_at: u64,
+}
+
+impl SecureStorageStatus {
+    fn of(chain: &SecretChain) -> Self {
+        let backend = chain.primary_kind();
+        Self {
+            backend,
+            persists_across_reboots: backend.is_some_and(BackendKind::persists),
+            last_error: chain.last_error(),
+            checked_at: chain.probed_at_ms,
+        }
     }
+}
+
+/// The secret chain, probed on first use and only probed again when the
+/// webview asks for it. The status is read off the chain it describes, so the
+/// two cannot disagree.
+#[derive(Default)]
+pub struct SecureStorageState {
+    chain: RwLock<Option<Arc<SecretChain>>>,
+}
 
-    pub fn get_secret(_key: &str) -> Result<Option<String>, String> {
-        Ok(None)
+impl SecureStorageState {
+    /// The current chain, probed on first use.
+    pub fn chain(&self) -> Arc<SecretChain> {
+        if let Some(chain) = self.chain.read().ok().and_then(|chain| chain.clone()) {
+            return chain;
+        }
+        let mut current = self.chain.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+        current.get_or_insert_with(|| Arc::new(SecretChain::detect())).clone()
     }
 
-    pub fn delete_secret(_key: &str) -> Result<(), String> {
-        Ok(())
+    /// Probes the backends again, e.g. after the user unlocked their keychain.
+    fn reprobe(&self) -> Arc<SecretChain> {
+        let chain = Arc::new(SecretChain::detect());
+        *self.chain.write().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(chain.clone());
+        chain
     }
 }
 
-#[cfg(not(target_os = "android"))]
-pub use desktop::{delete_secret, get_secret, set_secret};
+static VAULT_DIR: OnceLock<PathBuf> = OnceLock::new();
 
-#[cfg(target_os = "android")]
-pub use mobile::{delete_secret, get_secret, set_secret};
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Records the app data directory Tauri resolved, for platforms where `dirs`
+/// has none (Android). Must run before the first secret is accessed.
+pub fn init(app: &tauri::AppHandle) {
+    if let Ok(dir) = app.path().app_local_data_dir() {
+        let _ = VAULT_DIR.set(dir);
+    }
+}
+
+fn vault_dir() -> Option<PathBuf> {
+    #[cfg(target_os = "android")]
+    {
+        VAULT_DIR.get().cloned()
+    }
+
+    #[cfg(not(target_os = "android"))]
+    {
+        dirs::data_local_dir()
+            .or_else(dirs::data_dir)
+            .map(|dir| dir.join("journai"))
+            .or_else(|| VAULT_DIR.get().cloned())
+    }
+}
+
+/// Writes and reads back a probe value, so backends that accept writes
+/// without persisting them are rejected.
+fn probe(backend: &dyn SecretBackend) -> Result<(), String> {
+    if backend.get(AVAILABILITY_PROBE_KEY)?.as_deref() == Some(AVAILABILITY_PROBE_VALUE) {
+        return Ok(());
+    }
 
+    backend.set(AVAILABILITY_PROBE_KEY, AVAILABILITY_PROBE_VALUE)?;
+    match backend.get(AVAILABILITY_PROBE_KEY)? {
+        Some(stored) if stored == AVAILABILITY_PROBE_VALUE => Ok(()),
+        _ => Err("Stored probe value could not be read back".to_string()),
+    }
+}
+
+fn is_reserved_key(key: &str) -> bool {
+    RESERVED_KEY_PREFIXES.iter().any(|prefix| key.starts_with(prefix))
+}
+
+/// Resolves a key passed over IPC, rejecting reserved and undeclared keys.
+fn allowed_secret(key: &str) -> Result<&'static AllowedSecret, String> {
+    if is_reserved_key(key) {
+        return Err(format!("Secure storage key \"{key}\" is reserved"));
+    }
+    ALLOWED_SECRETS
+        .iter()
+        .find(|secret| secret.key == key)
+        .ok_or_else(|| format!("Secure storage key \"{key}\" is not allowed"))
+}
+
+/// Stores a secret, refusing to keep it only in process memory.
 #[tauri::command]
-pub async fn secure_storage_set(key: String, value: String) -> Result<(), String> {
-    tauri::async_runtime::spawn_blocking(move || set_secret(&key, &value))
-        .await
-        .map_err(|e| format!("Task failed: {e}"))?
+pub async fn secure_storage_set(key: String, value: String, app: tauri::AppHandle) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        allowed_secret(&key)?;
+        let state = app.state::<SecureStorageState>();
+        state.chain().set_persistent(&key, &value).map(|_| ())
+    })
+    .await
+    .map_err(|e| format!("Task failed: {e}"))?
 }
 
 #[tauri::command]
-pub async fn secure_storage_get(key: String) -> Result<Option<String>, String> {
-    tauri::async_runtime::spawn_blocking(move || get_secret(&key))
-        .await
-        .map_err(|e| format!("Task failed: {e}"))?
+pub async fn secure_storage_get(key: String, app: tauri::AppHandle) -> Result<Option<String>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        allowed_secret(&key)?;
+        app.state::<SecureStorageState>().chain().get(&key)
+    })
+    .await
+    .map_err(|e| format!("Task failed: {e}"))?
 }
 
 #[tauri::command]
-pub async fn secure_storage_delete(key: String) -> Result<(), String> {
-    tauri::async_runtime::spawn_blocking(move || delete_secret(&key))
-        .await
-        .map_err(|e| format!("Task failed: {e}"))?
+pub async fn secure_storage_delete(key: String, app: tauri::AppHandle) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        allowed_secret(&key)?;
+        app.state::<SecureStorageState>().chain().delete(&key)
+    })
+    .await
+    .map_err(|e| format!("Task failed: {e}"))?
 }
 
+/// Lists the secrets the webview may use and whether each is stored.
 #[tauri::command]
-pub async fn secure_storage_is_available() -> bool {
-    tauri::async_runtime::spawn_blocking(|| {
-        #[cfg(not(target_os = "android"))]
-        {
-            use keyring::Entry;
+pub async fn secure_storage_list(app: tauri::AppHandle) -> Result<Vec<SecretListEntry>, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let chain = app.state::<SecureStorageState>().chain();
+        ALLOWED_SECRETS
+            .iter()
+            .map(|secret| {
+                Ok(SecretListEntry {
+                    secret: *secret,
+                    present: chain.get(secret.key)?.is_some(),
+                })
+            })
+            .collect()
+    })
+    .await
+    .map_err(|e| format!("Task failed: {e}"))?
+}
 
-            let entry = match Entry::new(SERVICE_NAME, AVAILABILITY_PROBE_KEY) {
-                Ok(entry) => entry,
-                Err(_) => return false,
-            };
+/// Reports the backend in use. The chain is probed once and kept; pass
+/// `refresh` to probe the backends again.
+#[tauri::command]
+pub async fn secure_storage_status(
+    refresh: Option<bool>,
+    app: tauri::AppHandle,
+) -> Result<SecureStorageStatus, String> {
+    let refresh = refresh.unwrap_or(false);
+    tauri::async_runtime::spawn_blocking(move || {
+        let state = app.state::<SecureStorageState>();
+        let chain = if refresh { state.reprobe() } else { state.chain() };
+        SecureStorageStatus::of(&chain)
+    })
+    .await
+    .map_err(|e| format!("Task failed: {e}"))
+}
 
-            match entry.get_password() {
-                Ok(stored) if stored == AVAILABILITY_PROBE_VALUE => return true,
-                Err(keyring::Error::NoEntry) => {}
-                Ok(_) => {}
-                Err(_) => return false,
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            if entry.set_password(AVAILABILITY_PROBE_VALUE).is_err() {
-                return false;
-            }
+    struct FailingBackend;
 
-            let verify = match Entry::new(SERVICE_NAME, AVAILABILITY_PROBE_KEY) {
-                Ok(e) => e,
-                Err(_) => return false,
-            };
-            matches!(verify.get_password(), Ok(v) if v == AVAILABILITY_PROBE_VALUE)
+    impl SecretBackend for FailingBackend {
+        fn kind(&self) -> BackendKind {
+            BackendKind::Keychain
+        }
+
+        fn set(&self, _key: &str, _value: &str) -> Result<(), String> {
+            Err("keychain locked".to_string())
         }
-        #[cfg(target_os = "android")]
-        {
-            false
+
+        fn get(&self, _key: &str) -> Result<Option<String>, String> {
+            Err("keychain locked".to_string())
         }
-    })
-    .await
-    .unwrap_or(false)
+
+        fn delete(&self, _key: &str) -> Result<(), String> {
+            Err("keychain locked".to_string())
+        }
+    }
+
+    /// An in-memory backend that claims to persist, standing in for the vault.
+    #[derive(Default)]
+    struct DiskBackend(MemoryBackend);
+
+    impl SecretBackend for DiskBackend {
+        fn kind(&self) -> BackendKind {
+            BackendKind::File
+        }
+
+        fn set(&self, key: &str, value: &str) -> Result<(), String> {
+            self.0.set(key, value)
+        }
+
+        fn get(&self, key: &str) -> Result<Option<String>, String> {
+            self.0.get(key)
+        }
+
+        fn delete(&self, key: &str) -> Result<(), String> {
+            self.0.delete(key)
+        }
+    }
+
+    fn memory_chain(count: usize) -> SecretChain {
+        SecretChain::new(
+            (0..count)
+                .map(|_| Box::new(MemoryBackend::default()) as Box<dyn SecretBackend>)
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn set_falls_through_failing_backends() {
+        let chain = SecretChain::new(vec![Box::new(FailingBackend), Box::new(MemoryBackend::default())]);
+
+        assert_eq!(chain.set("key", "value"), Ok(BackendKind::Memory));
+        assert_eq!(chain.locate("key"), Ok(Some(("value".to_string(), BackendKind::Memory))));
+        assert_eq!(chain.last_error().as_deref(), Some("keychain locked"));
+        assert!(chain.delete("key").is_err());
+    }
+
+    #[test]
+    fn set_persistent_rejects_memory_only_storage() {
+        let chain = SecretChain::new(vec![Box::new(FailingBackend), Box::new(MemoryBackend::default())]);
+
+        assert_eq!(chain.set_persistent("key", "value"), Err(NOT_PERSISTENT_ERROR.to_string()));
+        assert_eq!(chain.copies("key"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn set_removes_copies_from_other_backends() {
+        let chain = memory_chain(2);
+        chain.backends[1].set("key", "stale").unwrap();
+
+        chain.set("key", "fresh").unwrap();
+        assert_eq!(chain.copies("key"), vec!["fresh".to_string()]);
+    }
+
+    #[test]
+    fn mirror_writes_every_backend_and_delete_clears_them() {
+        let chain = SecretChain::new(vec![Box::new(DiskBackend::default()), Box::new(MemoryBackend::default())]);
+
+        assert_eq!(chain.mirror("key", "value"), Ok(BackendKind::File));
+        assert_eq!(chain.copies("key"), vec!["value".to_string(), "value".to_string()]);
+
+        chain.delete("key").unwrap();
+        assert_eq!(chain.get("key"), Ok(None));
+    }
+
+    #[test]
+    fn mirror_rolls_back_when_a_persistent_backend_fails() {
+        let chain = SecretChain::new(vec![
+            Box::new(DiskBackend::default()),
+            Box::new(FailingBackend),
+            Box::new(MemoryBackend::default()),
+        ]);
+        chain.backends[0].set("key", "old").unwrap();
+
+        assert_eq!(chain.mirror("key", "new"), Err("keychain locked".to_string()));
+        assert_eq!(chain.copies("key"), vec!["old".to_string()]);
+    }
+
+    #[test]
+    fn mirror_requires_a_persistent_backend() {
+        let chain = memory_chain(2);
+
+        assert_eq!(chain.mirror("key", "value"), Err(NOT_PERSISTENT_ERROR.to_string()));
+        assert_eq!(chain.copies("key"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn restore_puts_back_each_backends_copy() {
+        let chain = SecretChain::new(vec![Box::new(DiskBackend::default()), Box::new(MemoryBackend::default())]);
+        chain.backends[0].set("key", "old").unwrap();
+        let snapshot = chain.snapshot("key").unwrap();
+
+        chain.mirror("key", "new").unwrap();
+        chain.restore("key", &snapshot).unwrap();
+
+        assert_eq!(chain.snapshot("key").unwrap(), vec![Some("old".to_string()), None]);
+    }
+
+    #[test]
+    fn reserved_and_undeclared_keys_are_rejected() {
+        assert!(allowed_secret("journai.apiKey").is_ok());
+        assert!(allowed_secret("journai.app_lock.keyset").is_err());
+        assert!(allowed_secret("journai.app_lock.open_dek").is_err());
+        assert!(allowed_secret(AVAILABILITY_PROBE_KEY).is_err());
+        assert!(allowed_secret("journai.other").is_err());
+        assert!(ALLOWED_SECRETS.iter().all(|secret| !is_reserved_key(secret.key)));
+    }
+
+    #[test]
+    fn empty_chain_is_unsupported() {
+        let chain = SecretChain::new(Vec::new());
+
+        assert_eq!(chain.primary_kind(), None);
+        assert!(!SecureStorageStatus::of(&chain).persists_across_reboots);
+        assert_eq!(chain.set("key", "value"), Err(UNSUPPORTED_ERROR.to_string()));
+        assert_eq!(chain.get("key"), Err(UNSUPPORTED_ERROR.to_string()));
+    }
 }
@@ -0,0 +1,378 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use super::{BackendKind, SecretBackend};
+use crate::private_file::write_private_file;
+
+const VAULT_FILE_NAME: &str = "secret_vault.json";
+const VAULT_KEY_FILE_NAME: &str = "secret_vault.key";
+const VAULT_VERSION: u8 = 1;
+const VAULT_KEY_LENGTH: usize = 32;
+const NONCE_LENGTH: usize = 12;
+const SALT_LENGTH: usize = 16;
+/// The binding key only has to resist copying the directory to another
+/// machine, not offline guessing of the machine ID.
+const MACHINE_BINDING_MEMORY_KIB: u32 = 19 * 1024;
+const MACHINE_BINDING_ITERATIONS: u32 = 2;
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VaultFile {
+    version: u8,
+    nonce_b64: String,
+    ciphertext_b64: String,
+}
+
+/// A vault key wrapped under a key derived from the machine ID.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MachineBoundKeyFile {
+    version: u8,
+    salt_b64: String,
+    nonce_b64: String,
+    wrapped_key_b64: String,
+}
+
+/// Stable per-machine identifier used to bind the vault key.
+fn machine_secret() -> Option<Vec<u8>> {
+    #[cfg(all(unix, not(any(target_os = "macos", target_os = "ios", target_os = "android"))))]
+    {
+        ["/etc/machine-id", "/var/lib/dbus/machine-id"]
+            .iter()
+            .filter_map(|path| fs::read_to_string(path).ok())
+            .map(|id| id.trim().to_string())
+            .find(|id| !id.is_empty())
+            .map(String::into_bytes)
+    }
+
+    #[cfg(not(all(unix, not(any(target_os = "macos", target_os = "ios", target_os = "android")))))]
+    {
+        None
+    }
+}
+
+fn binding_cipher(machine_secret: &[u8], salt: &[u8]) -> Result<Aes256Gcm, String> {
+    let params = Params::new(
+        MACHINE_BINDING_MEMORY_KIB,
+        MACHINE_BINDING_ITERATIONS,
+        1,
+        Some(VAULT_KEY_LENGTH),
+    )
+    .map_err(|e| format!("Invalid Argon2 parameters: {e}"))?;
+    let mut binding_key = [0u8; VAULT_KEY_LENGTH];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(machine_secret, salt, &mut binding_key)
+        .map_err(|e| format!("Failed to derive key with Argon2id: {e}"))?;
+    Aes256Gcm::new_from_slice(&binding_key).map_err(|e| format!("Failed to initialize cipher: {e}"))
+}
+
+fn seal_key(key: &[u8; VAULT_KEY_LENGTH], machine_secret: &[u8]) -> Result<Vec<u8>, String> {
+    let mut salt = [0u8; SALT_LENGTH];
+    let mut nonce = [0u8; NONCE_LENGTH];
+    OsRng.fill_bytes(&mut salt);
+    OsRng.fill_bytes(&mut nonce);
+    let wrapped = binding_cipher(machine_secret, &salt)?
+        .encrypt(Nonce::from_slice(&nonce), key.as_ref())
+        .map_err(|e| format!("Failed to wrap secret vault key: {e}"))?;
+
+    serde_json::to_vec(&MachineBoundKeyFile {
+        version: VAULT_VERSION,
+        salt_b64: BASE64.encode(salt),
+        nonce_b64: BASE64.encode(nonce),
+        wrapped_key_b64: BASE64.encode(wrapped),
+    })
+    .map_err(|e| format!("Failed to serialize secret vault key: {e}"))
+}
+
+fn open_key(file: &MachineBoundKeyFile, machine_secret: &[u8]) -> Result<[u8; VAULT_KEY_LENGTH], String> {
+    let decode = |value: &str| BASE64.decode(value).map_err(|e| format!("Invalid secret vault key: {e}"));
+    let salt = decode(&file.salt_b64)?;
+    let nonce = decode(&file.nonce_b64)?;
+    if nonce.len() != NONCE_LENGTH {
+        return Err("Invalid secret vault key nonce length".to_string());
+    }
+
+    binding_cipher(machine_secret, &salt)?
+        .decrypt(Nonce::from_slice(&nonce), decode(&file.wrapped_key_b64)?.as_ref())
+        .map_err(|_| "Secret vault belongs to a different machine".to_string())?
+        .try_into()
+        .map_err(|_| "Invalid secret vault key length".to_string())
+}
+
+/// Secrets kept in an AES-256-GCM encrypted file, for platforms without a
+/// usable keychain.
+///
+/// The vault key lives in a separate owner-only file in the same directory, so
+/// the vault alone (e.g. in a synced folder or a backup) reveals nothing. Where
+/// the OS exposes a machine ID the key file is wrapped under it, so copying the
+/// whole directory to another machine does not expose the secrets either.
+pub struct EncryptedFileBackend {
+    vault_path: PathBuf,
+    key: [u8; VAULT_KEY_LENGTH],
+    machine_bound: bool,
+    lock: Mutex<()>,
+}
+
+impl EncryptedFileBackend {
+    /// Opens the vault in `dir`, creating its key on first use. A key written
+    /// before machine binding was available is wrapped on open. A vault whose
+    /// key is missing is refused, since a new key could not read it and the
+    /// next write would replace its secrets.
+    pub fn open(dir: &Path) -> Result<Self, String> {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create secret vault directory {}: {e}", dir.display()))?;
+
+        let key_path = dir.join(VAULT_KEY_FILE_NAME);
+        let vault_path = dir.join(VAULT_FILE_NAME);
+        let machine_secret = machine_secret();
+        let (key, stored_bound) = match fs::read(&key_path) {
+            Ok(bytes) => match serde_json::from_slice::<MachineBoundKeyFile>(&bytes) {
+                Ok(file) => {
+                    let secret = machine_secret
+                        .as_deref()
+                        .ok_or_else(|| "Secret vault is bound to an unavailable machine ID".to_string())?;
+                    (open_key(&file, secret)?, true)
+                }
+                Err(_) => (
+                    bytes
+                        .try_into()
+                        .map_err(|_| format!("Invalid secret vault key {}", key_path.display()))?,
+                    false,
+                ),
+            },
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                if vault_path.exists() {
+                    return Err(format!(
+                        "Secret vault {} exists but its key {} is missing",
+                        vault_path.display(),
+                        key_path.display()
+                    ));
+                }
+                let mut key = [0u8; VAULT_KEY_LENGTH];
+                OsRng.fill_bytes(&mut key);
+                if machine_secret.is_none() {
+                    write_private_file(&key_path, &key)?;
+                }
+                (key, false)
+            }
+            Err(e) => return Err(format!("Failed to read secret vault key {}: {e}", key_path.display())),
+        };
+
+        if let (false, Some(secret)) = (stored_bound, machine_secret.as_deref()) {
+            write_private_file(&key_path, &seal_key(&key, secret)?)?;
+        }
+
+        Ok(Self {
+            vault_path,
+            key,
+            machine_bound: machine_secret.is_some(),
+            lock: Mutex::new(()),
+        })
+    }
+
+    fn cipher(&self) -> Result<Aes256Gcm, String> {
+        Aes256Gcm::new_from_slice(&self.key).map_err(|e| format!("Failed to initialize cipher: {e}"))
+    }
+
+    fn load(&self) -> Result<BTreeMap<String, String>, String> {
+        let raw = match fs::read_to_string(&self.vault_path) {
+            Ok(raw) => raw,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(BTreeMap::new()),
+            Err(e) => return Err(format!("Failed to read secret vault {}: {e}", self.vault_path.display())),
+        };
+
+        let vault: VaultFile =
+            serde_json::from_str(&raw).map_err(|e| format!("Invalid secret vault: {e}"))?;
+        if vault.version != VAULT_VERSION {
+            return Err(format!("Unsupported secret vault version {}", vault.version));
+        }
+
+        let nonce = BASE64
+            .decode(&vault.nonce_b64)
+            .map_err(|e| format!("Invalid secret vault nonce: {e}"))?;
+        if nonce.len() != NONCE_LENGTH {
+            return Err("Invalid secret vault nonce length".to_string());
+        }
+        let ciphertext = BASE64
+            .decode(&vault.ciphertext_b64)
+            .map_err(|e| format!("Invalid secret vault ciphertext: {e}"))?;
+        let plaintext = self
+            .cipher()?
+            .decrypt(Nonce::from_slice(&nonce), ciphertext.as_ref())
+            .map_err(|_| "Secret vault could not be decrypted".to_string())?;
+
+        serde_json::from_slice(&plaintext).map_err(|e| format!("Invalid secret vault contents: {e}"))
+    }
+
+    fn save(&self, secrets: &BTreeMap<String, String>) -> Result<(), String> {
+        let plaintext =
+            serde_json::to_vec(secrets).map_err(|e| format!("Failed to serialize secret vault: {e}"))?;
+        let mut nonce = [0u8; NONCE_LENGTH];
+        OsRng.fill_bytes(&mut nonce);
+        let ciphertext = self
+            .cipher()?
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_ref())
+            .map_err(|e| format!("Failed to encrypt secret vault: {e}"))?;
+
+        let raw = serde_json::to_string(&VaultFile {
+            version: VAULT_VERSION,
+            nonce_b64: BASE64.encode(nonce),
+            ciphertext_b64: BASE64.encode(ciphertext),
+        })
+        .map_err(|e| format!("Failed to serialize secret vault: {e}"))?;
+        write_private_file(&self.vault_path, raw.as_bytes())
+    }
+
+    fn update(&self, apply: impl FnOnce(&mut BTreeMap<String, String>)) -> Result<(), String> {
+        let _guard = self
+            .lock
+            .lock()
+            .map_err(|_| "Failed to acquire secret vault lock".to_string())?;
+        let mut secrets = self.load()?;
+        apply(&mut secrets);
+        self.save(&secrets)
+    }
+}
+
+impl SecretBackend for EncryptedFileBackend {
+    fn kind(&self) -> BackendKind {
+        if self.machine_bound {
+            BackendKind::MachineBoundFile
+        } else {
+            BackendKind::File
+        }
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<(), String> {
+        self.update(|secrets| {
+            secrets.insert(key.to_string(), value.to_string());
+        })
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>, String> {
+        let _guard = self
+            .lock
+            .lock()
+            .map_err(|_| "Failed to acquire secret vault lock".to_string())?;
+        Ok(self.load()?.remove(key))
+    }
+
+    fn delete(&self, key: &str) -> Result<(), String> {
+        self.update(|secrets| {
+            secrets.remove(key);
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An empty directory in the temp dir, cleared of any earlier run.
+    fn empty_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("journai-vault-{name}"));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn secrets_round_trip() {
+        let dir = empty_dir("round-trip");
+        let vault = EncryptedFileBackend::open(&dir).unwrap();
+
+        assert_eq!(vault.get("key"), Ok(None));
+        vault.set("key", "value").unwrap();
+        vault.set("other", "kept").unwrap();
+        assert_eq!(vault.get("key"), Ok(Some("value".to_string())));
+
+        vault.delete("key").unwrap();
+        assert_eq!(vault.get("key"), Ok(None));
+        assert_eq!(vault.get("other"), Ok(Some("kept".to_string())));
+
+        let raw = fs::read_to_string(dir.join(VAULT_FILE_NAME)).unwrap();
+        assert!(!raw.contains("kept"));
+    }
+
+    #[test]
+    fn reopened_vault_reads_earlier_secrets() {
+        let dir = empty_dir("reopen");
+        let vault = EncryptedFileBackend::open(&dir).unwrap();
+        vault.set("key", "value").unwrap();
+        let kind = vault.kind();
+        drop(vault);
+
+        let reopened = EncryptedFileBackend::open(&dir).unwrap();
+        assert_eq!(reopened.kind(), kind);
+        assert_eq!(reopened.get("key"), Ok(Some("value".to_string())));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            for name in [VAULT_FILE_NAME, VAULT_KEY_FILE_NAME] {
+                let mode = fs::metadata(dir.join(name)).unwrap().permissions().mode();
+                assert_eq!(mode & 0o777, 0o600, "{name}");
+            }
+        }
+    }
+
+    #[test]
+    fn tampered_ciphertext_is_rejected() {
+        let dir = empty_dir("tampered-vault");
+        let vault = EncryptedFileBackend::open(&dir).unwrap();
+        vault.set("key", "value").unwrap();
+
+        let path = dir.join(VAULT_FILE_NAME);
+        let mut file: VaultFile = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        let mut ciphertext = BASE64.decode(&file.ciphertext_b64).unwrap();
+        ciphertext[0] ^= 0x01;
+        file.ciphertext_b64 = BASE64.encode(ciphertext);
+        fs::write(&path, serde_json::to_string(&file).unwrap()).unwrap();
+
+        assert_eq!(vault.get("key"), Err("Secret vault could not be decrypted".to_string()));
+        assert!(vault.set("other", "value").is_err());
+    }
+
+    #[test]
+    fn tampered_key_file_is_rejected() {
+        let dir = empty_dir("tampered-key");
+        EncryptedFileBackend::open(&dir)
+            .unwrap()
+            .set("key", "value")
+            .unwrap();
+
+        let path = dir.join(VAULT_KEY_FILE_NAME);
+        let mut bytes = fs::read(&path).unwrap();
+        let middle = bytes.len() / 2;
+        bytes[middle] ^= 0x01;
+        fs::write(&path, bytes).unwrap();
+
+        let read = EncryptedFileBackend::open(&dir).and_then(|vault| vault.get("key"));
+        assert!(read.is_err());
+    }
+
+    #[test]
+    fn vault_without_its_key_is_refused() {
+        let dir = empty_dir("missing-key");
+        EncryptedFileBackend::open(&dir)
+            .unwrap()
+            .set("key", "value")
+            .unwrap();
+        let vault = fs::read(dir.join(VAULT_FILE_NAME)).unwrap();
+        fs::remove_file(dir.join(VAULT_KEY_FILE_NAME)).unwrap();
+
+        assert!(EncryptedFileBackend::open(&dir).is_err());
+        assert!(!dir.join(VAULT_KEY_FILE_NAME).exists());
+        assert_eq!(fs::read(dir.join(VAULT_FILE_NAME)).unwrap(), vault);
+    }
+}
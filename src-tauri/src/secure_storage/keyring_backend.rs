@@ -0,0 +1,36 @@
+use keyring::Entry;
+
+use super::{BackendKind, SecretBackend, SERVICE_NAME};
+
+/// Secrets held by the OS keychain (Keychain, Credential Manager or the
+/// Secret Service).
+pub struct KeyringBackend;
+
+impl SecretBackend for KeyringBackend {
+    fn kind(&self) -> BackendKind {
+        BackendKind::Keychain
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<(), String> {
+        let entry = Entry::new(SERVICE_NAME, key).map_err(|e| e.to_string())?;
+        entry.set_password(value).map_err(|e| e.to_string())
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>, String> {
+        let entry = Entry::new(SERVICE_NAME, key).map_err(|e| e.to_string())?;
+        match entry.get_password() {
+            Ok(password) => Ok(Some(password)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    fn delete(&self, key: &str) -> Result<(), String> {
+        let entry = Entry::new(SERVICE_NAME, key).map_err(|e| e.to_string())?;
+        match entry.delete_credential() {
+            Ok(()) => Ok(()),
+            Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use super::{BackendKind, SecretBackend};
+
+/// Secrets held in process memory only, lost when the app exits. Used as the
+/// last link of the chain so callers still work when nothing persistent is
+/// available, and in tests.
+#[derive(Default)]
+pub struct MemoryBackend {
+    secrets: Mutex<HashMap<String, String>>,
+}
+
+impl MemoryBackend {
+    fn secrets(&self) -> Result<std::sync::MutexGuard<'_, HashMap<String, String>>, String> {
+        self.secrets
+            .lock()
+            .map_err(|_| "Failed to acquire in-memory secret store lock".to_string())
+    }
+}
+
+impl SecretBackend for MemoryBackend {
+    fn kind(&self) -> BackendKind {
+        BackendKind::Memory
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<(), String> {
+        self.secrets()?.insert(key.to_string(), value.to_string());
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>, String> {
+        Ok(self.secrets()?.get(key).cloned())
+    }
+
+    fn delete(&self, key: &str) -> Result<(), String> {
+        self.secrets()?.remove(key);
+        Ok(())
+    }
+}
@@ -8,19 +8,31 @@ import Spinner from './themed/Spinner';
 const MIN_PASSPHRASE_LENGTH = 8;
 
 export default function AppLockScreen() {
-  const { configured, unlock, configure } = useAppLock();
+  const { configured, unlock, unlockWithRecoveryKey, configure } = useAppLock();
   const [passphrase, setPassphrase] = useState('');
   const [confirmPassphrase, setConfirmPassphrase] = useState('');
+  const [recoveryKey, setRecoveryKey] = useState('');
+  const [useRecoveryKey, setUseRecoveryKey] = useState(false);
   const [submitting, setSubmitting] = useState(false);
   const [error, setError] = useState<string | null>(null);
 
+  const choosingPassphrase = !configured || useRecoveryKey;
+
+  const toggleRecoveryKey = () => {
+    setUseRecoveryKey((current) => !current);
+    setPassphrase('');
+    setConfirmPassphrase('');
+    setRecoveryKey('');
+    setError(null);
+  };
+
   const handleSubmit = async (event: FormEvent) => {
     event.preventDefault();
     setSubmitting(true);
     setError(null);
 
     try {
-      if (!configured) {
+      if (choosingPassphrase) {
         const normalized = passphrase.trim();
         if (normalized.length < MIN_PASSPHRASE_LENGTH) {
           setError(`Passphrase must be at least ${MIN_PASSPHRASE_LENGTH} characters.`);
@@ -32,7 +44,16 @@ export default function AppLockScreen() {
           return;
         }
 
-        await configure(normalized);
+        if (configured) {
+          const ok = await unlockWithRecoveryKey(recoveryKey.trim(), normalized);
+          if (!ok) {
+            setError('Incorrect recovery key. Check it and try again.');
+            return;
+          }
+          setRecoveryKey('');
+        } else {
+          await configure(normalized);
+        }
         setPassphrase('');
         setConfirmPassphrase('');
       } else {
@@ -44,7 +65,9 @@ export default function AppLockScreen() {
         }
       }
     } catch (caughtError) {
-      const message = caughtError instanceof Error ? caughtError.message : null;
+      const message = caughtError instanceof Error
+        ? caughtError.message
+        : typeof caughtError === 'string' ? caughtError : null;
       setError(
         message
           || (configured
@@ -56,6 +79,21 @@ export default function AppLockScreen() {
     }
   };
 
+  const title = !configured
+    ? 'Secure JournAi'
+    : useRecoveryKey ? 'Reset your passphrase' : 'JournAi is locked';
+  const description = !configured
+    ? 'Set a passphrase to unlock the encrypted local database.'
+    : useRecoveryKey
+      ? 'Enter your recovery key and choose a new passphrase.'
+      : 'Enter your app passphrase to continue.';
+  const passphraseLabel = !configured
+    ? 'Create passphrase'
+    : useRecoveryKey ? 'New passphrase' : 'Passphrase';
+  const submitLabel = !configured
+    ? 'Set Passphrase'
+    : useRecoveryKey ? 'Reset Passphrase' : 'Unlock';
+
   return (
     <div className="app-lock-screen">
       <form className="app-lock-card" onSubmit={handleSubmit}>
@@ -63,17 +101,37 @@ export default function AppLockScreen() {
           <IoLockClosedOutline size={22} />
         </div>
         <Text as="h3" style={{ margin: 0 }}>
-          {configured ? 'JournAi is locked' : 'Secure JournAi'}
+          {title}
         </Text>
         <Text as="p" variant="secondary" style={{ margin: 0, fontSize: '0.9rem' }}>
-          {configured
-            ? 'Enter your app passphrase to continue.'
-            : 'Set a passphrase to unlock the encrypted local database.'}
+          {description}
         </Text>
 
+        {useRecoveryKey && (
+          <>
+            <Text as="span" variant="secondary" style={{ marginTop: '4px', fontSize: '0.85rem' }}>
+              <label htmlFor="app-lock-recovery-key">
+                Recovery key
+              </label>
+            </Text>
+            <input
+              id="app-lock-recovery-key"
+              type="text"
+              value={recoveryKey}
+              onChange={(event) => setRecoveryKey(event.target.value)}
+              className="app-lock-input"
+              autoComplete="off"
+              autoCapitalize="characters"
+              spellCheck={false}
+              autoFocus
+              disabled={submitting}
+            />
+          </>
+        )}
+
         <Text as="span" variant="secondary" style={{ marginTop: '4px', fontSize: '0.85rem' }}>
           <label htmlFor="app-lock-passphrase">
-            {configured ? 'Passphrase' : 'Create passphrase'}
+            {passphraseLabel}
           </label>
         </Text>
         <input
@@ -82,12 +140,12 @@ export default function AppLockScreen() {
           value={passphrase}
           onChange={(event) => setPassphrase(event.target.value)}
           className="app-lock-input"
-          autoComplete={configured ? 'current-password' : 'new-password'}
-          autoFocus
+          autoComplete={choosingPassphrase ? 'new-password' : 'current-password'}
+          autoFocus={!useRecoveryKey}
           disabled={submitting}
         />
 
-        {!configured && (
+        {choosingPassphrase && (
           <>
             <Text as="span" variant="secondary" style={{ marginTop: '4px', fontSize: '0.85rem' }}>
               <label htmlFor="app-lock-passphrase-confirm">
@@ -121,13 +179,24 @@ export default function AppLockScreen() {
           disabled={
             submitting
             || passphrase.trim().length === 0
-            || (!configured && confirmPassphrase.trim().length === 0)
+            || (choosingPassphrase && confirmPassphrase.trim().length === 0)
+            || (useRecoveryKey && recoveryKey.trim().length === 0)
           }
         >
-          {submitting
-            ? <Spinner size="sm" />
-            : configured ? 'Unlock' : 'Set Passphrase'}
+          {submitting ? <Spinner size="sm" /> : submitLabel}
         </Button>
+
+        {configured && (
+          <Button
+            type="button"
+            variant="ghost"
+            size="sm"
+            onClick={toggleRecoveryKey}
+            disabled={submitting}
+          >
+            {useRecoveryKey ? 'Use passphrase instead' : 'Forgot passphrase? Use recovery key'}
+          </Button>
+        )}
       </form>
     </div>
   );
@@ -0,0 +1,42 @@
+import Modal from './Modal';
+import { Button, Text } from './themed';
+import type { MigrationFailure } from '../lib/db';
+import '../styles/settings.css';
+
+interface MigrationFailedModalProps {
+  failure: MigrationFailure | null;
+  onClose: () => void;
+}
+
+export default function MigrationFailedModal({ failure, onClose }: MigrationFailedModalProps) {
+  if (!failure) {
+    return null;
+  }
+
+  const outcome = failure.restored
+    ? 'Your journal was restored to how it was before the update, so nothing was lost.'
+    : failure.backupPath
+      ? `The copy taken before the update could not be put back. It was kept at ${failure.backupPath}.`
+      : 'No copy of the database was taken before the update.';
+
+  return (
+    <Modal isOpen onClose={onClose} size="sm">
+      <div className="settings-modal-content">
+        <Text as="h3" variant="primary" className="settings-modal__title">
+          Database update failed
+        </Text>
+        <Text variant="secondary" className="settings-modal__description">
+          JournAi could not update its database: {failure.message}
+        </Text>
+        <Text variant="secondary" className="settings-modal__description">
+          {outcome}
+        </Text>
+        <div className="settings-modal__actions">
+          <Button variant="primary" size="sm" onClick={onClose}>
+            OK
+          </Button>
+        </div>
+      </div>
+    </Modal>
+  );
+}
@@ -21,16 +21,23 @@ export default function SecuritySection() {
   const { theme } = useTheme();
   const {
     configured,
+    openDekProtection,
     lockNow,
     configure,
     disable,
     changePassphrase,
+    regenerateRecoveryKey,
+    revokeRecoveryKey,
     lockTimeoutSeconds,
     setLockTimeout,
   } = useAppLock();
 
   const [setupPassphrase, setSetupPassphrase] = useState('');
   const [setupConfirm, setSetupConfirm] = useState('');
+  const [createRecoveryKey, setCreateRecoveryKey] = useState(true);
+  const [recoveryKey, setRecoveryKey] = useState<string | null>(null);
+  const [recoveryPassphrase, setRecoveryPassphrase] = useState('');
+  const [showRecoveryForm, setShowRecoveryForm] = useState(false);
   const [currentPassphrase, setCurrentPassphrase] = useState('');
   const [newPassphrase, setNewPassphrase] = useState('');
   const [newPassphraseConfirm, setNewPassphraseConfirm] = useState('');
@@ -60,6 +67,10 @@ export default function SecuritySection() {
     return normalizeInput(disablePassphrase).length > 0;
   }, [disablePassphrase]);
 
+  const canManageRecoveryKey = useMemo(() => {
+    return normalizeInput(recoveryPassphrase).length > 0;
+  }, [recoveryPassphrase]);
+
   const showStatus = (nextStatus: 'success' | 'error', message: string) => {
     setStatus(nextStatus);
     setStatusMessage(message);
@@ -74,9 +85,10 @@ export default function SecuritySection() {
     setStatus('saving');
     setStatusMessage('');
     try {
-      await configure(normalizeInput(setupPassphrase));
+      const createdKey = await configure(normalizeInput(setupPassphrase), { createRecoveryKey });
       setSetupPassphrase('');
       setSetupConfirm('');
+      setRecoveryKey(createdKey);
       showStatus('success', 'App lock enabled.');
     } catch (error) {
       showStatus('error', error instanceof Error ? error.message : 'Failed to enable app lock.');
@@ -118,6 +130,41 @@ export default function SecuritySection() {
     }
   };
 
+  const closeRecoveryForm = () => {
+    setShowRecoveryForm(false);
+    setRecoveryPassphrase('');
+  };
+
+  const handleRegenerateRecoveryKey = async () => {
+    if (!canManageRecoveryKey) return;
+
+    setStatus('saving');
+    setStatusMessage('');
+    try {
+      const createdKey = await regenerateRecoveryKey(normalizeInput(recoveryPassphrase));
+      closeRecoveryForm();
+      setRecoveryKey(createdKey);
+      showStatus('success', 'New recovery key created. The previous one no longer works.');
+    } catch (error) {
+      showStatus('error', typeof error === 'string' ? error : error instanceof Error ? error.message : 'Failed to create a recovery key.');
+    }
+  };
+
+  const handleRevokeRecoveryKey = async () => {
+    if (!canManageRecoveryKey) return;
+
+    setStatus('saving');
+    setStatusMessage('');
+    try {
+      await revokeRecoveryKey(normalizeInput(recoveryPassphrase));
+      closeRecoveryForm();
+      setRecoveryKey(null);
+      showStatus('success', 'Recovery key removed.');
+    } catch (error) {
+      showStatus('error', typeof error === 'string' ? error : error instanceof Error ? error.message : 'Failed to remove the recovery key.');
+    }
+  };
+
   const handleLockTimeoutChange = async (seconds: number) => {
     setStatus('saving');
     setStatusMessage('');
@@ -148,6 +195,16 @@ export default function SecuritySection() {
 
       {!configured ? (
         <>
+          {openDekProtection === 'unprotected' && (
+            <p className="settings-hint settings-hint--warning">
+              No system keychain is available, so the database key is stored unprotected on this device. Enable App Lock to encrypt it with a passphrase.
+            </p>
+          )}
+          {openDekProtection === 'machineBound' && (
+            <p className="settings-hint">
+              No system keychain is available. The database key is stored in a file bound to this machine.
+            </p>
+          )}
           <div className="settings-field">
             <label className="settings-label">New passphrase</label>
             <input
@@ -172,6 +229,21 @@ export default function SecuritySection() {
               disabled={status === 'saving'}
             />
           </div>
+          <div className="settings-toggle-row">
+            <label className="settings-toggle-label" htmlFor="security-create-recovery-key">
+              Create a recovery key
+            </label>
+            <label className="settings-switch">
+              <input
+                id="security-create-recovery-key"
+                type="checkbox"
+                checked={createRecoveryKey}
+                onChange={(event) => setCreateRecoveryKey(event.target.checked)}
+                disabled={status === 'saving'}
+              />
+              <span className="settings-switch__slider" />
+            </label>
+          </div>
           <div className="settings-footer">
             <Button
               variant="secondary"
@@ -212,7 +284,7 @@ export default function SecuritySection() {
               <Button
                 variant="danger"
                 size="sm"
-                onClick={() => { setShowDisableForm(true); setShowChangeForm(false); }}
+                onClick={() => { setShowDisableForm(true); setShowChangeForm(false); closeRecoveryForm(); }}
               >
                 Disable
               </Button>
@@ -262,7 +334,7 @@ export default function SecuritySection() {
               variant="secondary"
               size="sm"
               icon={<IoKeyOutline size={14} />}
-              onClick={() => { setShowChangeForm(true); setShowDisableForm(false); }}
+              onClick={() => { setShowChangeForm(true); setShowDisableForm(false); closeRecoveryForm(); }}
             >
               Change Passphrase
             </Button>
@@ -332,9 +404,82 @@ export default function SecuritySection() {
               </div>
             </>
           )}
+
+          <div className="settings-section-divider" />
+
+          {!showRecoveryForm ? (
+            <Button
+              variant="secondary"
+              size="sm"
+              icon={<IoKeyOutline size={14} />}
+              onClick={() => { setShowRecoveryForm(true); setShowChangeForm(false); setShowDisableForm(false); }}
+            >
+              Recovery Key
+            </Button>
+          ) : (
+            <>
+              <p className="settings-hint">
+                A recovery key unlocks JournAi and sets a new passphrase if you forget yours. Creating a new one stops the previous key from working.
+              </p>
+              <div className="settings-field">
+                <label className="settings-label">Current passphrase</label>
+                <input
+                  type="password"
+                  value={recoveryPassphrase}
+                  onChange={(event) => setRecoveryPassphrase(event.target.value)}
+                  className="settings-input settings-input--full-padding"
+                  style={{ backgroundColor: inputBg }}
+                  autoComplete="current-password"
+                  disabled={status === 'saving'}
+                />
+              </div>
+
+              <div className="settings-footer settings-security-actions">
+                <Button
+                  variant="secondary"
+                  size="sm"
+                  onClick={handleRegenerateRecoveryKey}
+                  disabled={!canManageRecoveryKey || status === 'saving'}
+                >
+                  {status === 'saving' ? <><Spinner size="sm" /> Saving...</> : 'Create New Key'}
+                </Button>
+                <Button
+                  variant="danger"
+                  size="sm"
+                  onClick={handleRevokeRecoveryKey}
+                  disabled={!canManageRecoveryKey || status === 'saving'}
+                >
+                  Remove Key
+                </Button>
+                <Button
+                  variant="ghost"
+                  size="sm"
+                  disabled={status === 'saving'}
+                  onClick={closeRecoveryForm}
+                >
+                  Cancel
+                </Button>
+              </div>
+            </>
+          )}
         </>
       )}
 
+      {recoveryKey && (
+        <div className="settings-field settings-recovery-key">
+          <label className="settings-label">Your recovery key</label>
+          <code className="settings-recovery-key__value">{recoveryKey}</code>
+          <p className="settings-hint settings-hint--warning">
+            Write this down and keep it somewhere safe. It is shown only once, and without it a forgotten passphrase means your journal cannot be recovered.
+          </p>
+          <div className="settings-footer">
+            <Button variant="secondary" size="sm" onClick={() => setRecoveryKey(null)}>
+              I&apos;ve Saved It
+            </Button>
+          </div>
+        </div>
+      )}
+
       {status === 'success' && (
         <span className="settings-status settings-status--success">
           <IoCheckmarkCircle size={14} /> {statusMessage}
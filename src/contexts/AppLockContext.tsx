@@ -8,20 +8,30 @@ import {
   useState,
   type ReactNode,
 } from 'react';
+import { listen } from '@tauri-apps/api/event';
 import {
   changeAppLockPassphrase,
   configureAppLock,
   disableAppLock,
   getAppLockStatus,
   lockApp,
+  type OpenDekProtection,
+  regenerateAppLockRecoveryKey,
+  revokeAppLockRecoveryKey,
+  sendAppLockHeartbeat,
+  setAppLockIdleTimeout,
   unlockAppLock,
+  unlockAppLockWithRecoveryKey,
 } from '../lib/appLock';
-import { closeDatabaseConnection } from '../lib/db';
+import { closeDatabaseConnection, type MigrationFailure, onMigrationFailed } from '../lib/db';
 import { appStore, STORE_KEYS } from '../lib/store';
+import MigrationFailedModal from '../components/MigrationFailedModal';
 
 const LOCK_TIMEOUT_OPTIONS = new Set([0, 60, 300]);
 const DEFAULT_LOCK_TIMEOUT_SECONDS = 300;
 const APP_LOCK_REQUIRED_EVENT = 'app-lock-required';
+const HEARTBEAT_INTERVAL_MS = 15_000;
+const ACTIVITY_EVENTS = ['keydown', 'pointerdown', 'wheel', 'touchstart'] as const;
 
 interface AppLockContextType {
   isReady: boolean;
@@ -29,9 +39,14 @@ interface AppLockContextType {
   unlocked: boolean;
   isLocked: boolean;
   lockTimeoutSeconds: number;
+  openDekProtection: OpenDekProtection | null;
   refreshStatus: () => Promise<void>;
-  configure: (passphrase: string) => Promise<void>;
+  /** Resolves to the recovery key when one was requested; it is shown only once. */
+  configure: (passphrase: string, options?: { createRecoveryKey?: boolean }) => Promise<string | null>;
   unlock: (passphrase: string) => Promise<boolean>;
+  unlockWithRecoveryKey: (recoveryKey: string, newPassphrase: string) => Promise<boolean>;
+  regenerateRecoveryKey: (passphrase: string) => Promise<string>;
+  revokeRecoveryKey: (passphrase: string) => Promise<void>;
   lockNow: () => Promise<void>;
   disable: (passphrase: string) => Promise<void>;
   changePassphrase: (currentPassphrase: string, newPassphrase: string) => Promise<void>;
@@ -52,14 +67,9 @@ export function AppLockProvider({ children }: { children: ReactNode }) {
   const [configured, setConfigured] = useState(false);
   const [unlocked, setUnlocked] = useState(true);
   const [lockTimeoutSeconds, setLockTimeoutSeconds] = useState(DEFAULT_LOCK_TIMEOUT_SECONDS);
-  const pendingLockTimerRef = useRef<number | null>(null);
-
-  const clearPendingLock = useCallback(() => {
-    if (pendingLockTimerRef.current !== null) {
-      window.clearTimeout(pendingLockTimerRef.current);
-      pendingLockTimerRef.current = null;
-    }
-  }, []);
+  const [openDekProtection, setOpenDekProtection] = useState<OpenDekProtection | null>(null);
+  const [migrationFailure, setMigrationFailure] = useState<MigrationFailure | null>(null);
+  const lastHeartbeatRef = useRef(0);
 
   const refreshStatus = useCallback(async () => {
     const [status, timeoutSetting] = await Promise.all([
@@ -69,17 +79,17 @@ export function AppLockProvider({ children }: { children: ReactNode }) {
 
     setConfigured(status.configured);
     setUnlocked(status.unlocked);
+    setOpenDekProtection(status.openDekProtection ?? null);
     setLockTimeoutSeconds(normalizeTimeout(timeoutSetting));
 
     if (!status.unlocked) {
-      clearPendingLock();
       try {
         await closeDatabaseConnection();
       } catch (error) {
         console.error('Failed to close database while refreshing app lock status:', error);
       }
     }
-  }, [clearPendingLock]);
+  }, []);
 
   useEffect(() => {
     let cancelled = false;
@@ -87,6 +97,8 @@ export function AppLockProvider({ children }: { children: ReactNode }) {
     const initialize = async () => {
       try {
         await refreshStatus();
+        const timeoutSetting = await appStore.get<number>(STORE_KEYS.SECURITY_LOCK_TIMEOUT_SECONDS);
+        await setAppLockIdleTimeout(normalizeTimeout(timeoutSetting));
       } catch (error) {
         console.error('Failed to initialize app lock status:', error);
       } finally {
@@ -100,9 +112,8 @@ export function AppLockProvider({ children }: { children: ReactNode }) {
 
     return () => {
       cancelled = true;
-      clearPendingLock();
     };
-  }, [clearPendingLock, refreshStatus]);
+  }, [refreshStatus]);
 
   useEffect(() => {
     const syncStatus = () => {
@@ -128,8 +139,6 @@ export function AppLockProvider({ children }: { children: ReactNode }) {
 
   useEffect(() => {
     const onAppLockRequired = () => {
-      clearPendingLock();
-
       void closeDatabaseConnection().catch((error) => {
         console.error('Failed to close database after lock-required event:', error);
       });
@@ -140,16 +149,35 @@ export function AppLockProvider({ children }: { children: ReactNode }) {
     };
 
     window.addEventListener(APP_LOCK_REQUIRED_EVENT, onAppLockRequired);
+    const unlisten = listen(APP_LOCK_REQUIRED_EVENT, onAppLockRequired);
 
     return () => {
       window.removeEventListener(APP_LOCK_REQUIRED_EVENT, onAppLockRequired);
+      void unlisten.then((fn) => fn());
     };
-  }, [clearPendingLock, refreshStatus]);
+  }, [refreshStatus]);
+
+  useEffect(() => {
+    const unlisten = onMigrationFailed((failure) => {
+      console.error('Secure database migration failed:', failure);
+      setMigrationFailure(failure);
+    });
 
-  const configure = useCallback(async (passphrase: string) => {
-    await configureAppLock(passphrase);
+    return () => {
+      void unlisten.then((fn) => fn());
+    };
+  }, []);
+
+  const dismissMigrationFailure = useCallback(() => {
+    setMigrationFailure(null);
+  }, []);
+
+  const configure = useCallback(async (passphrase: string, options: { createRecoveryKey?: boolean } = {}) => {
+    const recoveryKey = await configureAppLock(passphrase, options);
     setConfigured(true);
+    setOpenDekProtection(null);
     setUnlocked(true);
+    return recoveryKey;
   }, []);
 
   const unlock = useCallback(async (passphrase: string) => {
@@ -158,8 +186,23 @@ export function AppLockProvider({ children }: { children: ReactNode }) {
     return ok;
   }, []);
 
+  const unlockWithRecoveryKey = useCallback(async (recoveryKey: string, newPassphrase: string) => {
+    const ok = await unlockAppLockWithRecoveryKey(recoveryKey, newPassphrase);
+    setUnlocked(ok);
+    return ok;
+  }, []);
+
+  const regenerateRecoveryKey = useCallback(
+    (passphrase: string) => regenerateAppLockRecoveryKey(passphrase),
+    [],
+  );
+
+  const revokeRecoveryKey = useCallback(
+    (passphrase: string) => revokeAppLockRecoveryKey(passphrase),
+    [],
+  );
+
   const lockNow = useCallback(async () => {
-    clearPendingLock();
     setUnlocked(false);
     try {
       await closeDatabaseConnection();
@@ -173,14 +216,15 @@ export function AppLockProvider({ children }: { children: ReactNode }) {
       setUnlocked(true);
       throw error;
     }
-  }, [clearPendingLock]);
+  }, []);
 
   const disable = useCallback(async (passphrase: string) => {
     await disableAppLock(passphrase);
-    clearPendingLock();
     setConfigured(false);
     setUnlocked(true);
-  }, [clearPendingLock]);
+    const status = await getAppLockStatus();
+    setOpenDekProtection(status.openDekProtection ?? null);
+  }, []);
 
   const changePassphrase = useCallback(async (currentPassphrase: string, newPassphrase: string) => {
     await changeAppLockPassphrase(currentPassphrase, newPassphrase);
@@ -191,63 +235,37 @@ export function AppLockProvider({ children }: { children: ReactNode }) {
   const setLockTimeout = useCallback(async (seconds: number) => {
     const normalized = normalizeTimeout(seconds);
     await appStore.set(STORE_KEYS.SECURITY_LOCK_TIMEOUT_SECONDS, normalized);
+    await setAppLockIdleTimeout(normalized);
     setLockTimeoutSeconds(normalized);
   }, []);
 
   useEffect(() => {
     if (!configured || !unlocked) {
-      clearPendingLock();
       return;
     }
 
-    const scheduleLock = () => {
-      if (lockTimeoutSeconds === 0) {
-        void lockNow().catch((error) => {
-          console.error('Failed to lock app after background event:', error);
-        });
+    // The idle timer runs in Rust; user input only needs to keep it fresh.
+    const onActivity = () => {
+      const now = Date.now();
+      if (now - lastHeartbeatRef.current < HEARTBEAT_INTERVAL_MS) {
         return;
       }
-
-      clearPendingLock();
-      pendingLockTimerRef.current = window.setTimeout(() => {
-        pendingLockTimerRef.current = null;
-        void lockNow().catch((error) => {
-          console.error('Failed to lock app after timeout:', error);
-        });
-      }, lockTimeoutSeconds * 1000);
-    };
-
-    const cancelScheduledLock = () => {
-      clearPendingLock();
-    };
-
-    const onVisibilityChange = () => {
-      if (document.hidden) {
-        scheduleLock();
-      } else {
-        cancelScheduledLock();
-      }
-    };
-
-    const onBlur = () => {
-      scheduleLock();
-    };
-
-    const onFocus = () => {
-      cancelScheduledLock();
+      lastHeartbeatRef.current = now;
+      void sendAppLockHeartbeat().catch((error) => {
+        console.error('Failed to send app lock heartbeat:', error);
+      });
     };
 
-    document.addEventListener('visibilitychange', onVisibilityChange);
-    window.addEventListener('blur', onBlur);
-    window.addEventListener('focus', onFocus);
+    for (const eventName of ACTIVITY_EVENTS) {
+      window.addEventListener(eventName, onActivity, { passive: true });
+    }
 
     return () => {
-      document.removeEventListener('visibilitychange', onVisibilityChange);
-      window.removeEventListener('blur', onBlur);
-      window.removeEventListener('focus', onFocus);
-      cancelScheduledLock();
+      for (const eventName of ACTIVITY_EVENTS) {
+        window.removeEventListener(eventName, onActivity);
+      }
     };
-  }, [clearPendingLock, configured, lockNow, lockTimeoutSeconds, unlocked]);
+  }, [configured, unlocked]);
 
   const value = useMemo(
     () => ({
@@ -256,9 +274,13 @@ export function AppLockProvider({ children }: { children: ReactNode }) {
       unlocked,
       isLocked: configured && !unlocked,
       lockTimeoutSeconds,
+      openDekProtection,
       refreshStatus,
       configure,
       unlock,
+      unlockWithRecoveryKey,
+      regenerateRecoveryKey,
+      revokeRecoveryKey,
       lockNow,
       disable,
       changePassphrase,
@@ -269,9 +291,13 @@ export function AppLockProvider({ children }: { children: ReactNode }) {
       configured,
       unlocked,
       lockTimeoutSeconds,
+      openDekProtection,
       refreshStatus,
       configure,
       unlock,
+      unlockWithRecoveryKey,
+      regenerateRecoveryKey,
+      revokeRecoveryKey,
       lockNow,
       disable,
       changePassphrase,
@@ -279,7 +305,12 @@ export function AppLockProvider({ children }: { children: ReactNode }) {
     ],
   );
 
-  return <AppLockContext.Provider value={value}>{children}</AppLockContext.Provider>;
+  return (
+    <AppLockContext.Provider value={value}>
+      {children}
+      <MigrationFailedModal failure={migrationFailure} onClose={dismissMigrationFailure} />
+    </AppLockContext.Provider>
+  );
 }
 
 export function useAppLock() {
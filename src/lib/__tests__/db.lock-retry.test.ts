@@ -6,95 +6,127 @@ vi.mock('@tauri-apps/api/core', () => ({
   invoke: (...args: unknown[]) => mockInvoke(...args),
 }));
 
-describe('Database Lock Retry', () => {
+describe('Database Error Handling', () => {
   beforeEach(() => {
     vi.clearAllMocks();
     vi.resetModules();
   });
 
-  it('retries execute when sqlite reports a lock', async () => {
-    let executeAttempts = 0;
+  it('leaves busy retries to the plugin and surfaces a busy error once', async () => {
     mockInvoke.mockImplementation((command: string) => {
       if (command === 'plugin:sql|load') {
         return Promise.resolve('sqlite:journai.db');
       }
       if (command === 'plugin:sql|execute') {
-        executeAttempts += 1;
-        if (executeAttempts === 1) {
-          return Promise.reject(new Error('error returned from database: (code: 5) database is locked'));
-        }
-        return Promise.resolve({ rowsAffected: 1 });
+        return Promise.reject({ kind: 'busy', code: '5', message: 'error returned from database: (code: 5) database is locked' });
       }
       return Promise.resolve(undefined);
     });
 
-    const { execute } = await import('../db');
-    const result = await execute('DELETE FROM todos WHERE id = $1', ['todo-1']);
+    const { execute, DatabaseError } = await import('../db');
+    const result = execute('DELETE FROM todos WHERE id = $1', ['todo-1']);
 
-    expect(result).toEqual({ rowsAffected: 1 });
-    expect(mockInvoke).toHaveBeenCalledTimes(3);
+    await expect(result).rejects.toBeInstanceOf(DatabaseError);
+    await expect(result).rejects.toMatchObject({ kind: 'busy', code: '5' });
+    expect(mockInvoke).toHaveBeenCalledTimes(2);
   });
 
-  it('retries select when sqlite reports a lock', async () => {
-    let selectAttempts = 0;
+  it('does not re-invoke select when sqlite reports a lock', async () => {
     mockInvoke.mockImplementation((command: string) => {
       if (command === 'plugin:sql|load') {
         return Promise.resolve('sqlite:journai.db');
       }
       if (command === 'plugin:sql|select') {
-        selectAttempts += 1;
-        if (selectAttempts === 1) {
-          return Promise.reject(new Error('database is locked'));
-        }
-        return Promise.resolve([{ id: 'entry-1' }]);
+        return Promise.reject({ kind: 'busy', code: '5', message: 'database is locked' });
       }
       return Promise.resolve(undefined);
     });
 
     const { select } = await import('../db');
-    const rows = await select<{ id: string }>('SELECT id FROM entries');
 
-    expect(rows).toEqual([{ id: 'entry-1' }]);
-    expect(mockInvoke).toHaveBeenCalledTimes(3);
+    await expect(select('SELECT id FROM entries')).rejects.toMatchObject({ kind: 'busy' });
+    expect(mockInvoke).toHaveBeenCalledTimes(2);
   });
 
-  it('executes a batch inside one transaction with lock retry', async () => {
-    let insertAttempts = 0;
-    mockInvoke.mockImplementation((command: string, payload?: { query?: string }) => {
+  it('starts a select only after the writes queued before it', async () => {
+    let finishWrite!: () => void;
+    mockInvoke.mockImplementation((command: string) => {
       if (command === 'plugin:sql|load') {
         return Promise.resolve('sqlite:journai.db');
       }
-
       if (command === 'plugin:sql|execute') {
-        if (payload?.query === 'BEGIN IMMEDIATE') {
-          return Promise.resolve({ rowsAffected: 0 });
-        }
-        if (payload?.query?.startsWith('INSERT INTO todos')) {
-          insertAttempts += 1;
-          if (insertAttempts === 1) {
-            return Promise.reject(new Error('database is locked'));
-          }
-          return Promise.resolve({ rowsAffected: 1 });
-        }
-        if (payload?.query === 'COMMIT') {
-          return Promise.resolve({ rowsAffected: 0 });
-        }
+        return new Promise((resolve) => {
+          finishWrite = () => resolve([1, 1]);
+        });
+      }
+      if (command === 'plugin:sql|select') {
+        return Promise.resolve([{ id: 'todo-1' }]);
+      }
+      return Promise.resolve(undefined);
+    });
+
+    const { execute, select } = await import('../db');
+    const write = execute('INSERT INTO todos (id) VALUES ($1)', ['todo-1']);
+    const read = select('SELECT id FROM todos');
+
+    await vi.waitFor(() => expect(mockInvoke).toHaveBeenCalledWith('plugin:sql|execute', expect.anything()));
+    expect(mockInvoke).not.toHaveBeenCalledWith('plugin:sql|select', expect.anything());
+
+    finishWrite();
+    await write;
+    await expect(read).resolves.toEqual([{ id: 'todo-1' }]);
+    expect(mockInvoke.mock.calls.map(([command]) => command)).toEqual([
+      'plugin:sql|load',
+      'plugin:sql|execute',
+      'plugin:sql|select',
+    ]);
+  });
+
+  it('executes a batch as one transaction command', async () => {
+    mockInvoke.mockImplementation((command: string) => {
+      if (command === 'plugin:sql|load') {
+        return Promise.resolve('sqlite:journai.db');
+      }
+
+      if (command === 'plugin:sql|transaction') {
+        return Promise.resolve([[1, 1]]);
       }
 
-      return Promise.resolve({ rowsAffected: 0 });
+      return Promise.resolve(undefined);
     });
 
+    const statement = {
+      query: 'INSERT INTO todos (id, date, content, scheduled_time, completed, position, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)',
+      values: ['todo-1', '2025-01-01', 'Task', null, 0, 0, 'ts', 'ts'],
+    };
+
     const { executeBatch } = await import('../db');
-    await executeBatch([
-      {
-        query: 'INSERT INTO todos (id, date, content, scheduled_time, completed, position, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)',
-        values: ['todo-1', '2025-01-01', 'Task', null, 0, 0, 'ts', 'ts'],
-      },
-    ]);
+    await executeBatch([statement]);
 
-    expect(mockInvoke).toHaveBeenCalledTimes(5);
-    expect(mockInvoke).toHaveBeenNthCalledWith(2, 'plugin:sql|execute', expect.objectContaining({ query: 'BEGIN IMMEDIATE' }));
-    expect(mockInvoke).toHaveBeenNthCalledWith(5, 'plugin:sql|execute', expect.objectContaining({ query: 'COMMIT' }));
+    expect(mockInvoke).toHaveBeenCalledTimes(2);
+    expect(mockInvoke).toHaveBeenNthCalledWith(2, 'plugin:sql|transaction', {
+      db: 'sqlite:journai.db',
+      statements: [statement],
+    });
+  });
+
+  it('does not retry errors of other kinds', async () => {
+    mockInvoke.mockImplementation((command: string) => {
+      if (command === 'plugin:sql|load') {
+        return Promise.resolve('sqlite:journai.db');
+      }
+      if (command === 'plugin:sql|execute') {
+        return Promise.reject({ kind: 'constraint', code: '1811', message: 'sticky_notes.content cannot be empty' });
+      }
+      return Promise.resolve(undefined);
+    });
+
+    const { execute, DatabaseError } = await import('../db');
+    const result = execute('INSERT INTO sticky_notes (content) VALUES ($1)', ['']);
+
+    await expect(result).rejects.toBeInstanceOf(DatabaseError);
+    await expect(result).rejects.toMatchObject({ kind: 'constraint', code: '1811' });
+    expect(mockInvoke).toHaveBeenCalledTimes(2);
   });
 
   it('backs up and resets secure db once when load reports invalid database format', async () => {
@@ -103,7 +135,7 @@ describe('Database Lock Retry', () => {
       if (command === 'plugin:sql|load') {
         loadAttempts += 1;
         if (loadAttempts === 1) {
-          return Promise.reject(new Error('error returned from database: (code: 26) file is not a database'));
+          return Promise.reject({ kind: 'notADatabase', code: '26', message: 'error returned from database: (code: 26) file is not a database' });
         }
         return Promise.resolve('sqlite:journai.db');
       }
@@ -11,6 +11,15 @@ interface SecureStoragePayload {
   value?: string;
 }
 
+function storageStatus(persistsAcrossReboots: boolean) {
+  return {
+    backend: persistsAcrossReboots ? 'keychain' : 'memory',
+    persistsAcrossReboots,
+    lastError: persistsAcrossReboots ? null : 'Keychain unavailable',
+    checkedAt: 0,
+  };
+}
+
 describe('secureStorage API key persistence', () => {
   beforeEach(() => {
     vi.resetModules();
@@ -23,8 +32,8 @@ describe('secureStorage API key persistence', () => {
 
     mockInvoke.mockImplementation(async (command: string, payload?: SecureStoragePayload) => {
       switch (command) {
-        case 'secure_storage_is_available':
-          return true;
+        case 'secure_storage_status':
+          return storageStatus(true);
         case 'secure_storage_get':
           return payload?.key === 'journai.apiKey' ? secureApiKey : null;
         case 'secure_storage_set':
@@ -53,8 +62,8 @@ describe('secureStorage API key persistence', () => {
   it('does not read API key from localStorage', async () => {
     mockInvoke.mockImplementation(async (command: string, payload?: SecureStoragePayload) => {
       switch (command) {
-        case 'secure_storage_is_available':
-          return true;
+        case 'secure_storage_status':
+          return storageStatus(true);
         case 'secure_storage_get':
           return payload?.key === 'journai.apiKey' ? null : null;
         case 'secure_storage_set':
@@ -75,7 +84,7 @@ describe('secureStorage API key persistence', () => {
   it('returns remediation status when secure storage is unavailable', async () => {
     mockInvoke.mockImplementation(async (command: string) => {
       switch (command) {
-        case 'secure_storage_is_available':
+        case 'secure_storage_status':
           return null;
         case 'secure_storage_get':
           return null;
@@ -101,8 +110,8 @@ describe('secureStorage API key persistence', () => {
 
     mockInvoke.mockImplementation(async (command: string) => {
       switch (command) {
-        case 'secure_storage_is_available':
-          return storageAvailable;
+        case 'secure_storage_status':
+          return storageStatus(storageAvailable);
         case 'secure_storage_get':
           return null;
         case 'secure_storage_set':
@@ -131,8 +140,8 @@ describe('secureStorage API key persistence', () => {
 
     mockInvoke.mockImplementation(async (command: string, payload?: SecureStoragePayload) => {
       switch (command) {
-        case 'secure_storage_is_available':
-          return true;
+        case 'secure_storage_status':
+          return storageStatus(true);
         case 'secure_storage_get':
           return payload?.key === 'journai.apiKey' ? secureApiKey : null;
         case 'secure_storage_set':
@@ -1,8 +1,14 @@
 import { invoke } from '@tauri-apps/api/core';
 
+export type OpenDekProtection = 'keychain' | 'machineBound' | 'unprotected';
+
 export interface AppLockStatus {
   configured: boolean;
   unlocked: boolean;
+  failedAttempts?: number;
+  remainingAttempts?: number | null;
+  nextAttemptAt?: number | null;
+  openDekProtection?: OpenDekProtection | null;
 }
 
 function isTauriRuntime(): boolean {
@@ -28,14 +34,31 @@ export async function getAppLockStatus(): Promise<AppLockStatus> {
   }
 }
 
-export async function configureAppLock(passphrase: string): Promise<void> {
-  await invoke('app_lock_configure', { passphrase });
+export async function configureAppLock(
+  passphrase: string,
+  options: { createRecoveryKey?: boolean } = {},
+): Promise<string | null> {
+  const args = options.createRecoveryKey ? { passphrase, createRecoveryKey: true } : { passphrase };
+  const recoveryKey = await invoke<string | null>('app_lock_configure', args);
+  return recoveryKey ?? null;
 }
 
 export async function unlockAppLock(passphrase: string): Promise<boolean> {
   return invoke<boolean>('app_lock_unlock', { passphrase });
 }
 
+export async function unlockAppLockWithRecoveryKey(recoveryKey: string, newPassphrase: string): Promise<boolean> {
+  return invoke<boolean>('app_lock_unlock_with_recovery', { recoveryKey, newPassphrase });
+}
+
+export async function regenerateAppLockRecoveryKey(passphrase: string): Promise<string> {
+  return invoke<string>('app_lock_regenerate_recovery_key', { passphrase });
+}
+
+export async function revokeAppLockRecoveryKey(passphrase: string): Promise<void> {
+  await invoke('app_lock_revoke_recovery_key', { passphrase });
+}
+
 export async function lockApp(): Promise<void> {
   await invoke('app_lock_lock');
 }
@@ -47,3 +70,29 @@ export async function disableAppLock(passphrase: string): Promise<void> {
 export async function changeAppLockPassphrase(currentPassphrase: string, newPassphrase: string): Promise<void> {
   await invoke('app_lock_change_passphrase', { currentPassphrase, newPassphrase });
 }
+
+export async function setAppLockWipePolicy(maxFailedAttempts: number | null): Promise<void> {
+  await invoke('app_lock_set_wipe_policy', { maxFailedAttempts });
+}
+
+export interface AppLockKdfPolicy {
+  memoryKib: number;
+  iterations: number;
+  parallelism: number;
+}
+
+export async function calibrateAppLockKdf(targetMs?: number): Promise<AppLockKdfPolicy> {
+  return invoke<AppLockKdfPolicy>('app_lock_calibrate_kdf', { targetMs });
+}
+
+export async function rotateAppLockDataKey(passphrase?: string): Promise<string | null> {
+  return invoke<string | null>('app_lock_rotate_data_key', { passphrase });
+}
+
+export async function sendAppLockHeartbeat(): Promise<void> {
+  await invoke('app_lock_heartbeat');
+}
+
+export async function setAppLockIdleTimeout(timeoutSeconds: number | null): Promise<void> {
+  await invoke('app_lock_set_idle_timeout', { timeoutSeconds });
+}
@@ -1,40 +1,113 @@
-import { invoke } from '@tauri-apps/api/core';
+import { Channel, invoke, type InvokeArgs } from '@tauri-apps/api/core';
+import { listen, type UnlistenFn } from '@tauri-apps/api/event';
 import type { PaginatedResult, CursorConfig, PaginationOptions } from '../types/pagination';
 
 export const DB_URL = 'sqlite:journai.db';
-const DB_LOCK_MAX_RETRIES = 10;
-const DB_LOCK_BASE_DELAY_MS = 40;
 const APP_LOCK_REQUIRED_EVENT = 'app-lock-required';
+const MIGRATION_FAILED_EVENT = 'sql://migration-failed';
 
 let operationQueue: Promise<void> = Promise.resolve();
 let loadPromise: Promise<void> | null = null;
 let dbLoaded = false;
 let attemptedInvalidDatabaseRecovery = false;
 
+export interface SelectOptions {
+    /** Return BLOB columns as base64 strings instead of arrays of bytes. */
+    blobEncoding?: 'array' | 'base64';
+}
+
+export interface SelectStreamOptions extends SelectOptions {
+    /** Rows per chunk; the plugin defaults to 256. */
+    chunkSize?: number;
+    /** Stops the stream at its next chunk when aborted. */
+    signal?: AbortSignal;
+}
+
+export interface StreamSummary {
+    rows: number;
+    cancelled: boolean;
+}
+
 export interface DbStatement {
     query: string;
     values?: unknown[];
 }
 
-function sleep(ms: number): Promise<void> {
-    return new Promise((resolve) => {
-        setTimeout(resolve, ms);
+export type DatabaseErrorKind =
+    | 'busy'
+    | 'notADatabase'
+    | 'missingSessionKey'
+    | 'constraint'
+    | 'migration'
+    | 'notLoaded'
+    | 'invalidInput'
+    | 'database'
+    | 'other';
+
+/** An error reported by the SQL plugin. */
+export class DatabaseError extends Error {
+    readonly kind: DatabaseErrorKind;
+    /** The driver's error code, e.g. the SQLite extended result code. */
+    readonly code: string | null;
+
+    constructor(kind: DatabaseErrorKind, code: string | null, message: string) {
+        super(message);
+        this.name = 'DatabaseError';
+        this.kind = kind;
+        this.code = code;
+    }
+}
+
+function toDatabaseError(error: unknown): unknown {
+    if (typeof error !== 'object' || error === null || !('kind' in error) || !('message' in error)) {
+        return error;
+    }
+
+    const { kind, code, message } = error as { kind: DatabaseErrorKind; code?: string | null; message: string };
+    return new DatabaseError(kind, code ?? null, message);
+}
+
+/** Why the secure database failed to migrate, from the SQL plugin's event. */
+export interface MigrationFailure {
+    kind: DatabaseErrorKind;
+    code: string | null;
+    message: string;
+    /** Whether the database was put back as it was before the migrations ran. */
+    restored: boolean;
+    /** The pre-migration copy of the database, kept when it could not be restored. */
+    backupPath: string | null;
+}
+
+/**
+ * Calls `handler` when loading the secure database fails because a migration
+ * failed. The plugin has already restored the database when it could.
+ */
+export function onMigrationFailed(handler: (failure: MigrationFailure) => void): Promise<UnlistenFn> {
+    return listen<MigrationFailure & { db: string }>(MIGRATION_FAILED_EVENT, (event) => {
+        if (event.payload.db === DB_URL) {
+            handler(event.payload);
+        }
     });
 }
 
-function isDatabaseLockedError(error: unknown): boolean {
-    const message = String(error).toLowerCase();
-    return message.includes('database is locked') || message.includes('code: 5');
+async function invokeSql<T>(command: string, args?: InvokeArgs): Promise<T> {
+    try {
+        return await invoke<T>(`plugin:sql|${command}`, args);
+    } catch (error) {
+        throw toDatabaseError(error);
+    }
+}
+
+function isDatabaseErrorKind(error: unknown, kind: DatabaseErrorKind): boolean {
+    return error instanceof DatabaseError && error.kind === kind;
 }
 
 function isMissingSqlCipherKeyError(error: unknown): boolean {
-    const message = String(error).toLowerCase();
-    return message.includes('missing sqlcipher key');
+    return isDatabaseErrorKind(error, 'missingSessionKey');
 }
 
 function isInvalidDatabaseFormatError(error: unknown): boolean {
-    const message = String(error).toLowerCase();
-    return message.includes('file is not a database') || message.includes('code: 26');
+    return isDatabaseErrorKind(error, 'notADatabase');
 }
 
 function notifyAppLockRequired() {
@@ -55,7 +128,7 @@ async function recoverInvalidDatabaseFile(): Promise<boolean> {
     dbLoaded = false;
 
     try {
-        await invoke('plugin:sql|close', { db: DB_URL }).catch(() => undefined);
+        await invokeSql('close', { db: DB_URL }).catch(() => undefined);
         const backupPath = await invoke<string | null>('app_lock_backup_and_reset_secure_db');
         if (backupPath) {
             console.warn('[DB] Existing secure database was backed up after invalid format error:', backupPath);
@@ -69,15 +142,13 @@ async function recoverInvalidDatabaseFile(): Promise<boolean> {
     }
 }
 
-async function withDatabaseLockRetry<T>(operation: () => Promise<T>): Promise<T> {
-    let lastError: unknown;
-
-    for (let attempt = 0; attempt <= DB_LOCK_MAX_RETRIES; attempt++) {
+// Busy databases are retried inside the SQL plugin, so this only recovers from
+// an unreadable database file and asks for an unlock when the key is missing.
+async function withDatabaseRecovery<T>(operation: () => Promise<T>): Promise<T> {
+    for (;;) {
         try {
             return await operation();
         } catch (error) {
-            lastError = error;
-
             if (isInvalidDatabaseFormatError(error) && await recoverInvalidDatabaseFile()) {
                 continue;
             }
@@ -86,16 +157,9 @@ async function withDatabaseLockRetry<T>(operation: () => Promise<T>): Promise<T>
                 notifyAppLockRequired();
             }
 
-            if (!isDatabaseLockedError(error) || attempt === DB_LOCK_MAX_RETRIES) {
-                throw error;
-            }
-
-            const backoffMs = DB_LOCK_BASE_DELAY_MS * (attempt + 1);
-            await sleep(backoffMs);
+            throw error;
         }
     }
-
-    throw lastError;
 }
 
 async function runSerialized<T>(operation: () => Promise<T>): Promise<T> {
@@ -121,7 +185,7 @@ async function ensureDatabaseLoaded(): Promise<void> {
 
     if (!loadPromise) {
         loadPromise = (async () => {
-            await invoke('plugin:sql|load', { db: DB_URL });
+            await invokeSql('load', { db: DB_URL });
             dbLoaded = true;
             attemptedInvalidDatabaseRecovery = false;
         })();
@@ -143,14 +207,17 @@ async function ensureDatabaseLoaded(): Promise<void> {
     }
 }
 
-async function invokeSelect(query: string, values: unknown[]): Promise<unknown> {
+async function invokeSelect(query: string, values: unknown[], options?: SelectOptions): Promise<unknown> {
     await ensureDatabaseLoaded();
-    return invoke('plugin:sql|select', { db: DB_URL, query, values });
+    if (options?.blobEncoding) {
+        return invokeSql('select', { db: DB_URL, query, values, blobEncoding: options.blobEncoding });
+    }
+    return invokeSql('select', { db: DB_URL, query, values });
 }
 
 async function invokeExecute(query: string, values: unknown[]): Promise<{ rowsAffected: number }> {
     await ensureDatabaseLoaded();
-    const result = await invoke('plugin:sql|execute', { db: DB_URL, query, values });
+    const result = await invokeSql('execute', { db: DB_URL, query, values });
     const rowsAffected = Array.isArray(result) ? result[0] : (result as { rowsAffected: number }).rowsAffected;
     return { rowsAffected };
 }
@@ -171,51 +238,155 @@ export async function closeDatabaseConnection(): Promise<void> {
             return;
         }
 
-        await invoke('plugin:sql|close', { db: DB_URL });
+        await invokeSql('close', { db: DB_URL });
         loadPromise = null;
         dbLoaded = false;
     });
 }
 
-export async function select<T>(query: string, values: unknown[] = []): Promise<T[]> {
-    const result = await runSerialized(() => withDatabaseLockRetry(
-        () => invokeSelect(query, values)
-    ));
+/**
+ * Rolls the schema back or forward to migration `version` (0 undoes every
+ * migration). The plugin backs the database up first and returns the path of
+ * the backup. If a migration fails, the backup is moved back and the error
+ * message says whether that worked or where the backup was kept.
+ */
+export async function migrateTo(version: number): Promise<string | null> {
+    return runSerialized(() => withDatabaseRecovery(async () => {
+        await ensureDatabaseLoaded();
+        return invokeSql<string | null>('migrate_to', { db: DB_URL, version });
+    }));
+}
+
+// Reads run on the plugin's read-only connections, so unlike writes they are
+// not queued behind each other. They still wait for the writes already queued,
+// so a caller reads its own earlier writes.
+export async function select<T>(query: string, values: unknown[] = [], options?: SelectOptions): Promise<T[]> {
+    await operationQueue;
+    const result = await withDatabaseRecovery(
+        () => invokeSelect(query, values, options)
+    );
     return result as T[];
 }
 
+/**
+ * Streams the rows of a SELECT to `onChunk` a chunk at a time. The next chunk
+ * is only read once `onChunk` has settled, and an error thrown from it cancels
+ * the stream. Like `select`, it starts after the writes already queued and
+ * holds one read-only connection until it ends.
+ */
+export async function selectStream<T>(
+    query: string,
+    values: unknown[],
+    onChunk: (rows: T[]) => void | Promise<void>,
+    options: SelectStreamOptions = {}
+): Promise<StreamSummary> {
+    await operationQueue;
+    return withDatabaseRecovery(() => invokeSelectStream(query, values, onChunk, options));
+}
+
+async function invokeSelectStream<T>(
+    query: string,
+    values: unknown[],
+    onChunk: (rows: T[]) => void | Promise<void>,
+    options: SelectStreamOptions
+): Promise<StreamSummary> {
+    await ensureDatabaseLoaded();
+
+    const channel = new Channel<T[]>();
+    const cancel = () => {
+        void invokeSql('select_stream_cancel', { id: channel.id });
+    };
+
+    // The plugin sends an empty chunk after the last one.
+    let ended!: () => void;
+    const end = new Promise<void>((resolve) => {
+        ended = resolve;
+    });
+    let handled = Promise.resolve();
+    let failure: { error: unknown } | undefined;
+    channel.onmessage = (rows) => {
+        if (rows.length === 0) {
+            ended();
+            return;
+        }
+        handled = handled.then(async () => {
+            if (failure) {
+                return;
+            }
+            try {
+                await onChunk(rows);
+                await invokeSql('select_stream_ack', { id: channel.id });
+            } catch (error) {
+                failure = { error };
+                cancel();
+            }
+        });
+    };
+
+    const { signal } = options;
+    if (signal?.aborted) {
+        cancel();
+    }
+    signal?.addEventListener('abort', cancel, { once: true });
+    try {
+        const summary = await invokeSql<StreamSummary>('select_stream', {
+            db: DB_URL,
+            query,
+            values,
+            blobEncoding: options.blobEncoding,
+            chunkSize: options.chunkSize,
+            onChunk: channel,
+        });
+
+        // The response can overtake the last chunks on the channel.
+        await end;
+        await handled;
+        if (failure) {
+            throw failure.error;
+        }
+        return summary;
+    } finally {
+        signal?.removeEventListener('abort', cancel);
+    }
+}
+
 export async function execute(query: string, values: unknown[] = []): Promise<{ rowsAffected: number }> {
-    return runSerialized(() => withDatabaseLockRetry(
+    return runSerialized(() => withDatabaseRecovery(
         () => invokeExecute(query, values)
     ));
 }
 
+export async function executeMany(query: string, rows: unknown[][]): Promise<{ rowsAffected: number }> {
+    if (rows.length === 0) {
+        return { rowsAffected: 0 };
+    }
+
+    return runSerialized(() => withDatabaseRecovery(async () => {
+        await ensureDatabaseLoaded();
+        const result = await invokeSql<{ rowsAffected: number }>('execute_batch', {
+            db: DB_URL,
+            query,
+            values: rows,
+        });
+        return { rowsAffected: result.rowsAffected };
+    }));
+}
+
 export async function executeBatch(statements: DbStatement[]): Promise<void> {
     if (statements.length === 0) {
         return;
     }
 
-    await runSerialized(async () => {
-        await withDatabaseLockRetry(() => invokeExecute('BEGIN IMMEDIATE', []));
-        let committed = false;
-
-        try {
-            for (const statement of statements) {
-                await withDatabaseLockRetry(() => invokeExecute(statement.query, statement.values ?? []));
-            }
-
-            await withDatabaseLockRetry(() => invokeExecute('COMMIT', []));
-            committed = true;
-        } finally {
-            if (!committed) {
-                try {
-                    await withDatabaseLockRetry(() => invokeExecute('ROLLBACK', []));
-                } catch {
-                    // Ignore rollback errors for already-failed batches.
-                }
-            }
-        }
-    });
+    await runSerialized(() => withDatabaseRecovery(async () => {
+        await ensureDatabaseLoaded();
+        await invokeSql('transaction', {
+            db: DB_URL,
+            statements: statements.map((statement) => ({
+                query: statement.query,
+                values: statement.values ?? [],
+            })),
+        });
+    }));
 }
 
 export function parseCursor(cursor: string, config: CursorConfig): string[] {
@@ -1,18 +1,39 @@
 import { invoke } from '@tauri-apps/api/core';
 
+export interface SecureStorageEntry {
+  key: string;
+  label: string;
+  description: string;
+  present: boolean;
+}
+
+export type SecureStorageBackend = 'keychain' | 'machineBoundFile' | 'file' | 'memory';
+
+export interface SecureStorageStatus {
+  backend: SecureStorageBackend | null;
+  persistsAcrossReboots: boolean;
+  lastError: string | null;
+  checkedAt: number;
+}
+
 class SecureStorage {
   private available: boolean | null = null;
 
+  async status(refresh: boolean = false): Promise<SecureStorageStatus | null> {
+    try {
+      return (await invoke<SecureStorageStatus | null>('secure_storage_status', { refresh })) ?? null;
+    } catch {
+      return null;
+    }
+  }
+
   async isAvailable(forceRefresh: boolean = false): Promise<boolean> {
     if (forceRefresh) {
       this.available = null;
     }
     if (this.available !== null) return this.available;
-    try {
-      this.available = await invoke<boolean>('secure_storage_is_available');
-    } catch {
-      this.available = false;
-    }
+    const status = await this.status(forceRefresh);
+    this.available = status?.persistsAcrossReboots === true;
     return this.available;
   }
 
@@ -28,6 +49,10 @@ class SecureStorage {
   async delete(key: string): Promise<void> {
     await invoke('secure_storage_delete', { key });
   }
+
+  async list(): Promise<SecureStorageEntry[]> {
+    return invoke<SecureStorageEntry[]>('secure_storage_list');
+  }
 }
 
 export const secureStorage = new SecureStorage();
@@ -8,6 +8,17 @@ const mockSave = vi.fn();
 
 vi.mock('../../lib/db', () => ({
   select: (...args: unknown[]) => mockSelect(...args),
+  selectStream: async (
+    query: string,
+    _values: unknown[],
+    onChunk: (rows: unknown[]) => Promise<void> | void
+  ) => {
+    const rows: unknown[] = await mockSelect(query);
+    if (rows.length > 0) {
+      await onChunk(rows);
+    }
+    return { rows: rows.length, cancelled: false };
+  },
 }));
 
 vi.mock('@tauri-apps/plugin-fs', () => ({
@@ -22,6 +33,13 @@ vi.mock('@tauri-apps/plugin-dialog', () => ({
 
 import { exportData, selectExportDestination } from '../export';
 
+function writtenContent(path: string): string {
+  return mockWriteTextFile.mock.calls
+    .filter((call) => call[0] === path)
+    .map((call) => call[1])
+    .join('');
+}
+
 describe('Export Service', () => {
   beforeEach(() => {
     vi.clearAllMocks();
@@ -67,11 +85,11 @@ describe('Export Service', () => {
     expect(result.stickyNotesExported).toBe(1);
     expect(result.files).toEqual(['/tmp/journai-export.json']);
 
-    expect(mockWriteTextFile).toHaveBeenCalledTimes(1);
-    expect(mockWriteTextFile).toHaveBeenCalledWith(
-      '/tmp/journai-export.json',
-      expect.stringContaining('"schemaVersion": 1')
-    );
+    const written = writtenContent('/tmp/journai-export.json');
+    expect(written).toContain('"schemaVersion": 1');
+    expect(JSON.parse(written).todos).toEqual([
+      { date: '2025-01-01', content: 'Todo one', completed: true, scheduledTime: '08:00' },
+    ]);
   });
 
   it('exports CSV files for entries, todos, and sticky notes', async () => {
@@ -88,11 +106,9 @@ describe('Export Service', () => {
     ]);
 
     expect(mockMkdir).toHaveBeenCalledWith('/tmp/export-folder', { recursive: true });
-    expect(mockWriteTextFile).toHaveBeenCalledTimes(3);
-
-    const todosCall = mockWriteTextFile.mock.calls.find((call) => call[0] === '/tmp/export-folder/todos.csv');
-    expect(todosCall?.[1]).toContain('date,content,completed,scheduled_time');
-    expect(todosCall?.[1]).toContain('2025-01-01,Todo one,true,08:00');
+    const todosCsv = writtenContent('/tmp/export-folder/todos.csv');
+    expect(todosCsv).toContain('date,content,completed,scheduled_time');
+    expect(todosCsv).toContain('2025-01-01,Todo one,true,08:00');
   });
 
   it('selects destination using save for JSON and open for CSV folders', async () => {
@@ -134,8 +150,7 @@ describe('Export Service', () => {
       "SELECT date, content FROM sticky_notes WHERE TRIM(content) != '' ORDER BY date ASC, created_at ASC"
     );
 
-    const writtenJson = mockWriteTextFile.mock.calls[0][1];
-    const payload = JSON.parse(writtenJson);
+    const payload = JSON.parse(writtenContent('/tmp/journai-export.json'));
 
     expect(payload.stickyNotes).toEqual([{ date: '2025-01-01', content: 'Valid note' }]);
   });
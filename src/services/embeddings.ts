@@ -1,4 +1,4 @@
-import { select, execute } from '../lib/db';
+import { select, selectStream, execute, executeMany } from '../lib/db';
 import { getApiKey } from '../lib/secureStorage';
 import type { EmbeddingMetadata, EmbeddingStats } from '../types/memory';
 
@@ -79,14 +79,22 @@ export async function generateEmbeddingsBatch(texts: string[], apiKey: string):
   return data.data.map((d: { embedding: number[] }) => d.embedding);
 }
 
-function embeddingToBlob(embedding: number[]): Uint8Array {
-  const buffer = new Float32Array(embedding);
-  return new Uint8Array(buffer.buffer);
+function embeddingToBlob(embedding: number[]): { $blob: string } {
+  const bytes = new Uint8Array(new Float32Array(embedding).buffer);
+  let binary = '';
+  for (let i = 0; i < bytes.length; i++) {
+    binary += String.fromCharCode(bytes[i]);
+  }
+  return { $blob: btoa(binary) };
 }
 
-function blobToEmbedding(blob: Uint8Array): number[] {
-  const buffer = new Float32Array(blob.buffer);
-  return Array.from(buffer);
+function blobToEmbedding(blob: string): number[] {
+  const binary = atob(blob);
+  const bytes = new Uint8Array(binary.length);
+  for (let i = 0; i < binary.length; i++) {
+    bytes[i] = binary.charCodeAt(i);
+  }
+  return Array.from(new Float32Array(bytes.buffer));
 }
 
 export async function embedEntry(entryId: string, entryDate: string, content: string): Promise<number> {
@@ -101,16 +109,11 @@ export async function embedEntry(entryId: string, entryDate: string, content: st
   const embeddings = await generateEmbeddingsBatch(chunks, apiKey);
   const timestamp = new Date().toISOString();
 
-  for (let i = 0; i < chunks.length; i++) {
-    const id = generateId();
-    const blob = embeddingToBlob(embeddings[i]);
-
-    await execute(
-      `INSERT INTO embedding_chunks (id, entry_id, entry_date, content, embedding, chunk_index, created_at)
-       VALUES ($1, $2, $3, $4, $5, $6, $7)`,
-      [id, entryId, entryDate, chunks[i], blob, i, timestamp]
-    );
-  }
+  await executeMany(
+    `INSERT INTO embedding_chunks (id, entry_id, entry_date, content, embedding, chunk_index, created_at)
+     VALUES ($1, $2, $3, $4, $5, $6, $7)`,
+    chunks.map((chunk, i) => [generateId(), entryId, entryDate, chunk, embeddingToBlob(embeddings[i]), i, timestamp])
+  );
 
   return chunks.length;
 }
@@ -142,7 +145,7 @@ interface ChunkRow {
   entry_id: string;
   entry_date: string;
   content: string;
-  embedding: Uint8Array;
+  embedding: string;
   chunk_index: number;
 }
 
@@ -162,21 +165,28 @@ export async function searchByVector(
     values.push(dateRange.start, dateRange.end);
   }
 
-  const rows = await select<ChunkRow>(query, values);
-
-  const scored = rows.map(row => ({
-    id: row.id,
-    entryId: row.entry_id,
-    entryDate: row.entry_date,
-    content: row.content,
-    chunkIndex: row.chunk_index,
-    score: cosineSimilarity(queryEmbedding, blobToEmbedding(row.embedding)),
-  }));
-
-  scored.sort((a, b) => b.score - a.score);
+  // Only the best `limit` matches are kept while the table streams past.
+  let best: Array<EmbeddingMetadata & { score: number }> = [];
+  await selectStream<ChunkRow>(query, values, (rows) => {
+    for (const row of rows) {
+      const score = cosineSimilarity(queryEmbedding, blobToEmbedding(row.embedding));
+      if (score < minSimilarity) {
+        continue;
+      }
+      best.push({
+        id: row.id,
+        entryId: row.entry_id,
+        entryDate: row.entry_date,
+        content: row.content,
+        chunkIndex: row.chunk_index,
+        score,
+      });
+    }
+    best.sort((a, b) => b.score - a.score);
+    best = best.slice(0, limit);
+  }, { blobEncoding: 'base64' });
 
-  const filtered = scored.filter(r => r.score >= minSimilarity);
-  return filtered.slice(0, limit);
+  return best;
 }
 
 export async function getEmbeddingStats(): Promise<EmbeddingStats> {
@@ -1,7 +1,7 @@
 import { open, save } from '@tauri-apps/plugin-dialog';
 import { mkdir, writeTextFile } from '@tauri-apps/plugin-fs';
 import Papa from 'papaparse';
-import { select } from '../lib/db';
+import { select, selectStream } from '../lib/db';
 
 export type ExportFormat = 'json_bundle' | 'csv_folder';
 
@@ -57,11 +57,24 @@ function ensureJsonExtension(path: string): string {
   return `${path}.json`;
 }
 
+const ENTRIES_QUERY = 'SELECT date, content FROM entries ORDER BY date ASC, created_at ASC';
+const TODOS_QUERY = 'SELECT date, content, scheduled_time, completed FROM todos ORDER BY date ASC, position ASC';
+const STICKY_NOTES_QUERY = "SELECT date, content FROM sticky_notes WHERE TRIM(content) != '' ORDER BY date ASC, created_at ASC";
+
+function exportTodo(todo: TodoRow) {
+  return {
+    date: todo.date,
+    content: todo.content,
+    completed: todo.completed === 1,
+    scheduledTime: todo.scheduled_time,
+  };
+}
+
 async function loadExportData(): Promise<ExportDataSet> {
   const [entries, todos, stickyNotes] = await Promise.all([
-    select<EntryRow>('SELECT date, content FROM entries ORDER BY date ASC, created_at ASC'),
-    select<TodoRow>('SELECT date, content, scheduled_time, completed FROM todos ORDER BY date ASC, position ASC'),
-    select<StickyNoteRow>("SELECT date, content FROM sticky_notes WHERE TRIM(content) != '' ORDER BY date ASC, created_at ASC"),
+    select<EntryRow>(ENTRIES_QUERY),
+    select<TodoRow>(TODOS_QUERY),
+    select<StickyNoteRow>(STICKY_NOTES_QUERY),
   ]);
 
   return {
@@ -71,14 +84,58 @@ async function loadExportData(): Promise<ExportDataSet> {
   };
 }
 
-function toCsv<T extends Record<string, string>>(rows: T[], columns: string[]): string {
+function toCsv<T extends Record<string, string>>(rows: T[], columns: string[], header = true): string {
   return Papa.unparse(rows, {
     columns,
-    header: true,
+    header,
     newline: '\n',
   });
 }
 
+/**
+ * Streams a query into `path` as the value of `key` in a JSON bundle, laid out
+ * exactly as `JSON.stringify(payload, null, 2)` would. Returns the row count.
+ */
+async function appendJsonArray<TRow>(
+  path: string,
+  key: string,
+  query: string,
+  toItem: (row: TRow) => unknown,
+  isLast: boolean
+): Promise<number> {
+  let count = 0;
+  await writeTextFile(path, `  ${JSON.stringify(key)}: [`, { append: true });
+  await selectStream<TRow>(query, [], async (rows) => {
+    const items = rows.map((row) => {
+      const item = JSON.stringify(toItem(row), null, 2).replace(/\n/g, '\n    ');
+      return `${count++ === 0 ? '' : ','}\n    ${item}`;
+    });
+    await writeTextFile(path, items.join(''), { append: true });
+  });
+  await writeTextFile(path, `${count > 0 ? '\n  ' : ''}]${isLast ? '' : ','}\n`, { append: true });
+  return count;
+}
+
+/** Streams a query into a CSV file at `path`. Returns the row count. */
+async function writeCsvFile<TRow>(
+  path: string,
+  query: string,
+  toRecord: (row: TRow) => Record<string, string>,
+  columns: string[]
+): Promise<number> {
+  let count = 0;
+  await writeTextFile(path, '');
+  await selectStream<TRow>(query, [], async (rows) => {
+    const csv = toCsv(rows.map(toRecord), columns, count === 0);
+    await writeTextFile(path, count === 0 ? csv : `\n${csv}`, { append: true });
+    count += rows.length;
+  });
+  if (count === 0) {
+    await writeTextFile(path, toCsv([], columns), { append: true });
+  }
+  return count;
+}
+
 export async function selectExportDestination(format: ExportFormat): Promise<string | null> {
   if (format === 'json_bundle') {
     const date = new Date().toISOString().slice(0, 10);
@@ -121,12 +178,7 @@ export async function generateJsonExportContent(
     schemaVersion: 1,
     exportedAt: new Date().toISOString(),
     entries: data.entries,
-    todos: data.todos.map((todo) => ({
-      date: todo.date,
-      content: todo.content,
-      completed: todo.completed === 1,
-      scheduledTime: todo.scheduled_time,
-    })),
+    todos: data.todos.map(exportTodo),
     stickyNotes: data.stickyNotes,
   };
 
@@ -156,36 +208,37 @@ export async function exportData(
   };
 
   try {
-    const totalSteps = request.format === 'json_bundle' ? 2 : 5;
+    const totalSteps = 4;
     let currentStep = 0;
     onProgress?.(currentStep, totalSteps);
 
-    const data = await loadExportData();
-    currentStep += 1;
-    onProgress?.(currentStep, totalSteps);
-
-    result.entriesExported = data.entries.length;
-    result.todosExported = data.todos.length;
-    result.stickyNotesExported = data.stickyNotes.length;
-
     if (request.format === 'json_bundle') {
       const outputPath = ensureJsonExtension(request.destinationPath);
-      const payload = {
+      const header = {
         schemaVersion: 1,
         exportedAt: new Date().toISOString(),
-        entries: data.entries,
-        todos: data.todos.map((todo) => ({
-          date: todo.date,
-          content: todo.content,
-          completed: todo.completed === 1,
-          scheduledTime: todo.scheduled_time,
-        })),
-        stickyNotes: data.stickyNotes,
       };
+      await writeTextFile(outputPath, `${JSON.stringify(header, null, 2).slice(0, -2)},\n`);
+      currentStep += 1;
+      onProgress?.(currentStep, totalSteps);
 
-      await writeTextFile(outputPath, `${JSON.stringify(payload, null, 2)}\n`);
-      result.files.push(outputPath);
+      result.entriesExported = await appendJsonArray<EntryRow>(
+        outputPath, 'entries', ENTRIES_QUERY, (entry) => entry, false
+      );
+      currentStep += 1;
+      onProgress?.(currentStep, totalSteps);
+
+      result.todosExported = await appendJsonArray<TodoRow>(
+        outputPath, 'todos', TODOS_QUERY, exportTodo, false
+      );
+      currentStep += 1;
+      onProgress?.(currentStep, totalSteps);
 
+      result.stickyNotesExported = await appendJsonArray<StickyNoteRow>(
+        outputPath, 'stickyNotes', STICKY_NOTES_QUERY, (note) => note, true
+      );
+      await writeTextFile(outputPath, '}\n', { append: true });
+      result.files.push(outputPath);
       currentStep += 1;
       onProgress?.(currentStep, totalSteps);
 
@@ -200,40 +253,43 @@ export async function exportData(
     const todosPath = joinPath(request.destinationPath, 'todos.csv');
     const stickyNotesPath = joinPath(request.destinationPath, 'sticky_notes.csv');
 
-    const entriesCsv = toCsv(
-      data.entries.map((entry) => ({
+    result.entriesExported = await writeCsvFile<EntryRow>(
+      entriesPath,
+      ENTRIES_QUERY,
+      (entry) => ({
         date: entry.date,
         content: entry.content,
-      })),
+      }),
       ['date', 'content']
     );
-    await writeTextFile(entriesPath, entriesCsv);
     result.files.push(entriesPath);
     currentStep += 1;
     onProgress?.(currentStep, totalSteps);
 
-    const todosCsv = toCsv(
-      data.todos.map((todo) => ({
+    result.todosExported = await writeCsvFile<TodoRow>(
+      todosPath,
+      TODOS_QUERY,
+      (todo) => ({
         date: todo.date,
         content: todo.content,
         completed: todo.completed === 1 ? 'true' : 'false',
         scheduled_time: todo.scheduled_time ?? '',
-      })),
+      }),
       ['date', 'content', 'completed', 'scheduled_time']
     );
-    await writeTextFile(todosPath, todosCsv);
     result.files.push(todosPath);
     currentStep += 1;
     onProgress?.(currentStep, totalSteps);
 
-    const stickyNotesCsv = toCsv(
-      data.stickyNotes.map((note) => ({
+    result.stickyNotesExported = await writeCsvFile<StickyNoteRow>(
+      stickyNotesPath,
+      STICKY_NOTES_QUERY,
+      (note) => ({
         date: note.date,
         content: note.content,
-      })),
+      }),
       ['date', 'content']
     );
-    await writeTextFile(stickyNotesPath, stickyNotesCsv);
     result.files.push(stickyNotesPath);
     currentStep += 1;
     onProgress?.(currentStep, totalSteps);